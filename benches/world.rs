@@ -0,0 +1,259 @@
+//! Throughput of the dense storage container of the World compared to the sparse layout of boxed
+//! components, that was used before
+//!
+//! Both sides are bare containers with an index of entities, so locks and queries of the World
+//! are not measured
+use std::any::{Any, TypeId};
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use dotrix::world::Container;
+use dotrix::{Entity, Id};
+
+const ENTITIES: usize = 10_000;
+
+struct Position(f32, f32, f32);
+struct Velocity(f32, f32, f32);
+struct Health(u32);
+
+fn entity(i: usize) -> (Position, Velocity, Health) {
+    (
+        Position(i as f32, 0.0, 0.0),
+        Velocity(1.0, 1.0, 1.0),
+        Health(i as u32),
+    )
+}
+
+/// Sparse layout: every component is a separate heap allocation, removed slots are kept as holes
+///
+/// Like the dense container, it spawns entities from maps of boxed components and returns such maps
+/// on exile
+mod sparse {
+    use super::*;
+
+    type ComponentsList = Vec<Option<UnsafeCell<Box<dyn Any>>>>;
+
+    #[derive(Default)]
+    pub struct Container {
+        data: HashMap<TypeId, ComponentsList>,
+        alive: Vec<bool>,
+        removed: Vec<usize>,
+        index: HashMap<Id<Entity>, usize>,
+    }
+
+    impl Container {
+        pub fn spawn(
+            &mut self,
+            (position, velocity, health): (Position, Velocity, Health),
+        ) -> Id<Entity> {
+            let id = Id::<Entity>::new();
+            let slot = self.removed.pop().unwrap_or(self.alive.len());
+            let components = [
+                (TypeId::of::<Id<Entity>>(), Box::new(id) as Box<dyn Any>),
+                (TypeId::of::<Position>(), Box::new(position)),
+                (TypeId::of::<Velocity>(), Box::new(velocity)),
+                (TypeId::of::<Health>(), Box::new(health)),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+            for (type_id, component) in components {
+                let list = self.data.entry(type_id).or_default();
+                let cell = Some(UnsafeCell::new(component));
+                if slot < list.len() {
+                    list[slot] = cell;
+                } else {
+                    list.push(cell);
+                }
+            }
+            if slot < self.alive.len() {
+                self.alive[slot] = true;
+            } else {
+                self.alive.push(true);
+            }
+            self.index.insert(id, slot);
+            id
+        }
+
+        pub fn exile(&mut self, id: &Id<Entity>) -> Option<HashMap<TypeId, Box<dyn Any>>> {
+            self.index.remove(id).map(|slot| {
+                self.alive[slot] = false;
+                self.removed.push(slot);
+                self.data
+                    .iter_mut()
+                    .filter_map(|(type_id, list)| {
+                        list[slot]
+                            .take()
+                            .map(|component| (*type_id, component.into_inner()))
+                    })
+                    .collect()
+            })
+        }
+
+        pub fn update(&self) {
+            let positions = &self.data[&TypeId::of::<Position>()];
+            let velocities = &self.data[&TypeId::of::<Velocity>()];
+            let healths = &self.data[&TypeId::of::<Health>()];
+            for slot in 0..self.alive.len() {
+                if !self.alive[slot] {
+                    continue;
+                }
+                let (Some(position), Some(velocity), Some(health)) =
+                    (&positions[slot], &velocities[slot], &healths[slot])
+                else {
+                    continue;
+                };
+                let position = unsafe { (*position.get()).downcast_mut::<Position>().unwrap() };
+                let velocity = unsafe { (*velocity.get()).downcast_ref::<Velocity>().unwrap() };
+                let health = unsafe { (*health.get()).downcast_ref::<Health>().unwrap() };
+                if health.0 == 0 {
+                    continue;
+                }
+                position.0 += velocity.0;
+                position.1 += velocity.1;
+                position.2 += velocity.2;
+            }
+        }
+    }
+}
+
+/// Dense layout: the container of the World with swap-remove and index fix-up on exile
+mod dense {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct Container {
+        inner: Option<super::Container>,
+        index: HashMap<Id<Entity>, usize>,
+    }
+
+    impl Container {
+        pub fn spawn(&mut self, components: (Position, Velocity, Health)) -> Id<Entity> {
+            let id = Id::<Entity>::new();
+            let entity = Entity::new(components).with(id);
+            let row = self
+                .inner
+                .get_or_insert_with(|| super::Container::from(&entity))
+                .store(entity);
+            self.index.insert(id, row);
+            id
+        }
+
+        pub fn exile(&mut self, id: &Id<Entity>) -> Option<Entity> {
+            let row = self.index.remove(id)?;
+            let container = self.inner.as_mut()?;
+            let entity = container.remove(row);
+            let moved = container
+                .column::<Id<Entity>>()
+                .and_then(|ids| ids.get(row))
+                .map(|id| unsafe { *id.get() });
+            if let Some(moved) = moved {
+                self.index.insert(moved, row);
+            }
+            Some(entity)
+        }
+
+        pub fn update(&self) {
+            let Some(container) = self.inner.as_ref() else {
+                return;
+            };
+            let positions = container.column::<Position>().unwrap();
+            let velocities = container.column::<Velocity>().unwrap();
+            let healths = container.column::<Health>().unwrap();
+            for ((position, velocity), health) in positions.iter().zip(velocities).zip(healths) {
+                let position = unsafe { &mut *position.get() };
+                let velocity = unsafe { &*velocity.get() };
+                let health = unsafe { &*health.get() };
+                if health.0 == 0 {
+                    continue;
+                }
+                position.0 += velocity.0;
+                position.1 += velocity.1;
+                position.2 += velocity.2;
+            }
+        }
+    }
+}
+
+fn spawn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn");
+    group.bench_function("dense", |b| {
+        b.iter(|| {
+            let mut container = dense::Container::default();
+            for i in 0..ENTITIES {
+                container.spawn(entity(i));
+            }
+            black_box(container)
+        })
+    });
+    group.bench_function("sparse", |b| {
+        b.iter(|| {
+            let mut container = sparse::Container::default();
+            for i in 0..ENTITIES {
+                container.spawn(entity(i));
+            }
+            black_box(container)
+        })
+    });
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query");
+    let mut container = dense::Container::default();
+    for i in 0..ENTITIES {
+        container.spawn(entity(i));
+    }
+    group.bench_function("dense", |b| b.iter(|| container.update()));
+    let mut container = sparse::Container::default();
+    for i in 0..ENTITIES {
+        container.spawn(entity(i));
+    }
+    group.bench_function("sparse", |b| b.iter(|| container.update()));
+    group.finish();
+}
+
+fn exile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("exile");
+    // every second entity in order of spawning is exiled on both sides
+    group.bench_function("dense", |b| {
+        b.iter_batched(
+            || {
+                let mut container = dense::Container::default();
+                let ids = (0..ENTITIES)
+                    .map(|i| container.spawn(entity(i)))
+                    .collect::<Vec<_>>();
+                (container, ids)
+            },
+            |(mut container, ids)| {
+                for id in ids.iter().step_by(2) {
+                    black_box(container.exile(id));
+                }
+                container
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("sparse", |b| {
+        b.iter_batched(
+            || {
+                let mut container = sparse::Container::default();
+                let ids = (0..ENTITIES)
+                    .map(|i| container.spawn(entity(i)))
+                    .collect::<Vec<_>>();
+                (container, ids)
+            },
+            |(mut container, ids)| {
+                for id in ids.iter().step_by(2) {
+                    black_box(container.exile(id));
+                }
+                container
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, spawn, query, exile);
+criterion_main!(benches);
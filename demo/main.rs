@@ -54,9 +54,13 @@ impl dotrix::Application for Demo {
         // add Assets context
         scheduler.add_context(dotrix::Assets::default());
         // add World context
-        scheduler.add_context(dotrix::World::default());
+        scheduler.add_world(dotrix::World::default());
         // add spawner tasks
         scheduler.add_task(scene::SpawnEntities::default());
+        // add camera task, applying the viewpoint of the scene
+        scheduler.add_task(dotrix::loaders::ViewpointCamera);
+        // add hierarchy transforms propagation task
+        scheduler.add_task(dotrix::models::PropagateTransforms::default());
         // add rendering task
         scheduler.add_task(renderer);
     }
@@ -1,6 +1,6 @@
+use dotrix::loaders::SceneLoader;
 use dotrix::log;
-use dotrix::math::Vec3;
-use dotrix::{Id, Mut};
+use dotrix::{Id, Mut, ResourceFile};
 
 // NOTE: For one time spawn separate task is not optimal. In that case it would be better
 // to spawn everything before adding the World context. But one of purposes of this demo is to
@@ -23,38 +23,18 @@ impl dotrix::Task for SpawnEntities {
 
     fn run(&mut self, (mut assets, mut world): Self::Context) -> Self::Output {
         if self.spawned.is_null() {
-            let cube = assets.set(dotrix::Mesh::cube("Cube"));
-            let red_material = assets.set(dotrix::Material {
-                name: String::from("Red Material"),
-                albedo: dotrix::Color::red(),
-                ..Default::default()
-            });
-            let blue_material = assets.set(dotrix::Material {
-                name: String::from("Blue Material"),
-                albedo: dotrix::Color::blue(),
-                ..Default::default()
-            });
-            let entities = [
-                dotrix::Model {
-                    mesh: cube,
-                    material: red_material,
-                    scale: Vec3::new(0.5, 0.5, 0.5),
-                    ..Default::default()
-                },
-                dotrix::Model {
-                    mesh: cube,
-                    material: blue_material,
-                    scale: Vec3::new(0.3, 0.3, 0.3),
-                    translate: Vec3::new(1.0, 0.0, -0.4),
-                    ..Default::default()
-                },
-            ]
-            .into_iter()
-            .map(dotrix::Entity::from);
-
-            self.spawned = world
-                .spawn(entities)
+            let mut bundle = ResourceFile::new("demo/scene.toml", SceneLoader).read();
+            let scene = bundle
+                .extract::<dotrix::loaders::Scene>("demo")
+                .expect("The scene must be loaded");
+            for asset in bundle.bundle.into_values().flatten() {
+                assets.store(asset);
+            }
+
+            self.spawned = scene
+                .spawn(&mut world, &assets)
                 .last()
+                .copied()
                 .expect("The entity must be spawned");
 
             log::info!("Spawn scene entities: {:?}", self.spawned);
@@ -0,0 +1,317 @@
+//! Headless execution of tasks without a window and a display
+//!
+//! Useful for integration tests and dedicated servers, where the application logic must be
+//! advanced frame by frame without any presentation surface.
+
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use crate::graphics::{Extent2D, Frame};
+use crate::log;
+use crate::tasks::{Ref, Scheduler, Task, TaskManager};
+
+/// Maximal number of abandoned loops in a row, restarted by [`Headless::step`]
+pub const MAX_ABANDONED_LOOPS: u32 = 8;
+
+/// Headless frame clock, stored as a global context
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    /// Fixed time passed between two frames
+    pub delta: Duration,
+    /// Resolution reported by synthetic frames
+    pub resolution: Extent2D,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            delta: Duration::from_secs_f32(1.0 / 60.0),
+            resolution: Extent2D {
+                width: 800,
+                height: 600,
+            },
+        }
+    }
+}
+
+/// Task, responsible for synthetic frame creation
+pub struct CreateFrame {
+    frame_counter: u64,
+    started: Instant,
+}
+
+impl Default for CreateFrame {
+    fn default() -> Self {
+        Self {
+            frame_counter: 0,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Task for CreateFrame {
+    type Context = (Ref<Clock>,);
+    type Output = Frame;
+
+    fn run(&mut self, (clock,): Self::Context) -> Self::Output {
+        let frame_number = self.frame_counter + 1;
+        self.frame_counter = frame_number;
+
+        let frame = Frame {
+            fps: 1.0 / clock.delta.as_secs_f32(),
+            delta: clock.delta,
+            timestamp: self.started + clock.delta * frame_number as u32,
+            resolution: clock.resolution,
+            number: frame_number,
+            swapchain_index: 0,
+            scale_factor: 1.0,
+            resized: frame_number == 1,
+        };
+        log::debug!("headless::CreateFrame::run() -> {:?}", frame);
+        frame
+    }
+}
+
+/// Headless runner
+///
+/// `T` is a type of output, that completes the tasks cycle. The task providing it must use
+/// [`crate::tasks::OutputChannel::Scheduler`].
+pub struct Headless<T> {
+    task_manager: TaskManager,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Send + 'static> Headless<T> {
+    /// Creates headless runner with default [`Clock`]
+    pub fn new(workers: u32) -> Self {
+        Self::with_clock(workers, Clock::default())
+    }
+
+    /// Creates headless runner with custom [`Clock`]
+    pub fn with_clock(workers: u32, clock: Clock) -> Self {
+        let task_manager = TaskManager::new::<T>(workers);
+        {
+            let scheduler = task_manager.scheduler();
+            scheduler.add_context(clock);
+            scheduler.add_task(CreateFrame::default());
+        }
+        Self {
+            task_manager,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns scheduler to add tasks and contexts
+    pub fn scheduler(&self) -> Scheduler<'_> {
+        self.task_manager.scheduler()
+    }
+
+    /// Returns reference to the underlying [`TaskManager`]
+    pub fn task_manager(&self) -> &TaskManager {
+        &self.task_manager
+    }
+
+    /// Executes single tasks cycle and returns its final output
+    ///
+    /// Loops abandoned due to failures of tasks are restarted. Panics, if the output was not
+    /// provided in [`MAX_ABANDONED_LOOPS`] loops in a row.
+    pub fn step(&self) -> T {
+        let mut abandoned = 0;
+        loop {
+            self.task_manager.run();
+            match self.task_manager.wait_for::<T>() {
+                Ok(output) => return output,
+                Err(error) => {
+                    log::warn!("{}", error);
+                    abandoned += 1;
+                    if abandoned == MAX_ABANDONED_LOOPS {
+                        panic!("{}", error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Executes specified number of tasks cycles
+    pub fn run_frames(&self, frames: u64) {
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// Shuts the tasks execution down, see [`TaskManager::shutdown`]
+    pub fn shutdown(&mut self, timeout: Duration) -> bool {
+        self.task_manager.shutdown(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{Clock, Headless};
+    use crate::graphics::Frame;
+    use crate::tasks::{All, Any, FixedTick, FixedUpdate, Mut, OutputChannel, Task};
+
+    #[derive(Default)]
+    struct Counter {
+        frames: u64,
+        elapsed: std::time::Duration,
+    }
+
+    struct Done(u64);
+
+    struct CountFrames;
+
+    impl Task for CountFrames {
+        type Context = (Any<Frame>, Mut<Counter>);
+        type Output = Done;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (frame, mut counter): Self::Context) -> Self::Output {
+            counter.frames += 1;
+            counter.elapsed += frame.delta;
+            assert_eq!(counter.frames, frame.number);
+            Done(frame.number)
+        }
+    }
+
+    struct Simulate;
+
+    impl Task for Simulate {
+        type Context = (Any<FixedTick>, Mut<Counter>);
+        type Output = u64;
+
+        fn run(&mut self, (tick, mut counter): Self::Context) -> Self::Output {
+            counter.elapsed += tick.delta;
+            tick.number
+        }
+    }
+
+    struct Ticks(u32, usize);
+
+    struct CountTicks;
+
+    impl Task for CountTicks {
+        type Context = (Any<Frame>, Any<FixedUpdate>, All<u64>);
+        type Output = Ticks;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_frame, update, ticks): Self::Context) -> Self::Output {
+            Ticks(update.ticks, ticks.len())
+        }
+    }
+
+    #[test]
+    fn can_run_frames_without_display() {
+        let clock = Clock {
+            delta: std::time::Duration::from_millis(10),
+            ..Default::default()
+        };
+        let headless = Headless::<Done>::with_clock(2, clock);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.add_task(CountFrames);
+        }
+
+        assert_eq!(headless.step().0, 1);
+        headless.run_frames(8);
+        assert_eq!(headless.step().0, 10);
+
+        let counter = headless
+            .task_manager()
+            .remove_global_context::<Counter>()
+            .expect("Counter to be in the context");
+        assert_eq!(counter.frames, 10);
+        assert_eq!(counter.elapsed, std::time::Duration::from_millis(100));
+    }
+
+    #[test]
+    fn can_run_fixed_ticks_several_times_per_frame() {
+        let delta = std::time::Duration::from_millis(4);
+        let clock = Clock {
+            delta: std::time::Duration::from_millis(10),
+            ..Default::default()
+        };
+        let headless = Headless::<Ticks>::with_clock(2, clock);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.set_fixed_timestep(delta);
+            scheduler.add_task(Simulate);
+            scheduler.add_task(CountTicks);
+        }
+
+        // ticks are accumulated from the delta of the frame of the previous loop
+        let mut total_ticks = 0;
+        for expected in [0, 2, 3, 2, 3, 2, 3, 2] {
+            let Ticks(ticks, outputs) = headless.step();
+            assert_eq!(ticks, expected);
+            assert_eq!(ticks as usize, outputs);
+            total_ticks += ticks;
+            // time passed between the steps does not matter
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(total_ticks, 17);
+
+        let counter = headless
+            .task_manager()
+            .remove_global_context::<Counter>()
+            .expect("Counter to be in the context");
+        assert_eq!(counter.elapsed, delta * total_ticks);
+    }
+
+    /// Records its drop, `N` makes a distinct global type
+    struct Journal<const N: usize>(Arc<Mutex<Vec<&'static str>>>, &'static str);
+
+    impl<const N: usize> Drop for Journal<N> {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().push(self.1);
+        }
+    }
+
+    struct Teardown(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Task for Teardown {
+        type Context = (Any<Frame>,);
+        type Output = Done;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (frame,): Self::Context) -> Self::Output {
+            Done(frame.number)
+        }
+
+        fn shutdown(&mut self) {
+            self.0.lock().unwrap().push("task");
+        }
+    }
+
+    #[test]
+    fn can_shutdown_gracefully() {
+        let journal = Arc::new(Mutex::new(Vec::new()));
+        let mut headless = Headless::<Done>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Journal::<0>(Arc::clone(&journal), "first"));
+            scheduler.add_context(Counter::default());
+            scheduler.add_context(Journal::<1>(Arc::clone(&journal), "second"));
+            scheduler.add_task(Teardown(Arc::clone(&journal)));
+        }
+        headless.run_frames(3);
+
+        assert!(headless.shutdown(std::time::Duration::from_secs(5)));
+        assert_eq!(*journal.lock().unwrap(), ["task", "second", "first"]);
+        // second call is a no-op
+        assert!(headless.shutdown(std::time::Duration::from_secs(5)));
+    }
+}
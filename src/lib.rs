@@ -32,18 +32,26 @@ pub use features::*;
 /// Models abstractions
 pub mod models;
 pub use models::{
-    Animation, AnimationPlayer, AnimationState, Armature, Color, Image, Joint, Material, Mesh,
-    Model, RenderModels, Transform, VertexAttribute, VertexBitangent, VertexJoints, VertexNormal,
-    VertexPosition, VertexTangent, VertexTexture, VertexWeights,
+    Animation, AnimationPlayer, AnimationState, Armature, Color, GlobalTransform, Image, Joint,
+    Material, Mesh, Model, RenderModels, Transform, VertexAttribute, VertexBitangent, VertexJoints,
+    VertexNormal, VertexPosition, VertexTangent, VertexTexture, VertexWeights,
 };
 
 /// Rendering tools and routines
 pub mod graphics;
 pub use graphics::{DeviceType, Display, Extent2D, Format, Frame, Gpu, Semaphore};
 
+/// Headless execution for tests and servers
+pub mod headless;
+pub use headless::Headless;
+
 /// Tasks and execution
 pub mod tasks;
-pub use tasks::{All, Any, Mut, Output, Ref, State, Take, Task, TaskManager};
+pub use tasks::{
+    All, Any, AsyncTask, Condition, Errors, EventReader, Events, FailurePolicy, FallibleTask,
+    FixedTick, FixedUpdate, Mut, OnEnter, OnExit, Order, Output, Ref, State, Take, Task, TaskError,
+    TaskManager, Try,
+};
 
 /// Utils
 pub mod utils;
@@ -51,7 +59,7 @@ pub use utils::Id;
 
 /// World
 pub mod world;
-pub use world::{Camera, Entity, World};
+pub use world::{Camera, Commands, Entity, World};
 
 /// Window API and input events
 pub mod window;
@@ -7,6 +7,14 @@ pub use gltf_loader::GltfLoader;
 pub mod image_loader;
 pub use image_loader::ImageLoader;
 
+#[cfg(feature = "scene")]
+mod scene_loader;
+#[cfg(feature = "scene")]
+pub use scene_loader::{
+    CameraSetup, EntitySetup, ModelSetup, Scene, SceneLoader, ScenesSpawned, SpawnScene, Viewpoint,
+    ViewpointCamera,
+};
+
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
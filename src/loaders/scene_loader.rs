@@ -0,0 +1,628 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::graphics::{Extent2D, Frame};
+use crate::log;
+use crate::math::{EulerRot, Mat4, Quat, Vec3};
+use crate::models::{Color, Material, Mesh, Model};
+use crate::tasks::{Any, Mut, Ref, Task};
+use crate::utils::Id;
+use crate::world::{Camera, Entity, Lens, World};
+
+use super::{
+    Asset, Assets, GltfLoader, ImageLoader, ResourceBundle, ResourceLoader, ResourceReport,
+    ResourceTarget,
+};
+
+/// Scene description file
+#[derive(Deserialize)]
+struct SceneSetup {
+    /// Name of the scene, file name is used by default
+    name: Option<String>,
+    /// Resource files with assets, used by the scene
+    #[serde(default)]
+    resources: Vec<ResourceSetup>,
+    /// Materials defined by the scene
+    #[serde(default)]
+    materials: Vec<MaterialSetup>,
+    /// Meshes of primitive shapes defined by the scene
+    #[serde(default)]
+    meshes: Vec<MeshSetup>,
+    /// Entities to be spawned
+    #[serde(default)]
+    entities: Vec<EntitySetup>,
+}
+
+/// Resource file, referenced by the scene
+#[derive(Deserialize)]
+struct ResourceSetup {
+    /// Path to the file, relative to the scene file
+    path: String,
+}
+
+/// Material defined by the scene
+#[derive(Deserialize)]
+struct MaterialSetup {
+    name: String,
+    /// RGB or RGBA albedo color
+    #[serde(default)]
+    albedo: Option<Vec<f32>>,
+    metallic_factor: Option<f32>,
+    roughness_factor: Option<f32>,
+    occlusion_factor: Option<f32>,
+}
+
+/// Mesh of a primitive shape defined by the scene
+#[derive(Deserialize)]
+struct MeshSetup {
+    name: String,
+    shape: Shape,
+    /// Number of segments around the axis
+    #[serde(default = "MeshSetup::default_segments")]
+    segments: usize,
+    /// Number of rings along the axis
+    #[serde(default = "MeshSetup::default_rings")]
+    rings: usize,
+}
+
+impl MeshSetup {
+    fn default_segments() -> usize {
+        32
+    }
+
+    fn default_rings() -> usize {
+        16
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Shape {
+    Cube,
+    Sphere,
+    Hemisphere,
+    Cylinder,
+    Cone,
+}
+
+/// Entity described by the scene
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EntitySetup {
+    /// Name of the entity, used to refer to it as to a parent
+    pub name: Option<String>,
+    /// Name of the parent entity
+    pub parent: Option<String>,
+    /// Model of the entity
+    pub model: Option<ModelSetup>,
+    /// Camera of the entity
+    pub camera: Option<CameraSetup>,
+}
+
+/// [`Model`] with assets referenced by their names
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelSetup {
+    /// Name of the mesh
+    pub mesh: String,
+    /// Name of the material
+    pub material: Option<String>,
+    /// Name of the armature
+    pub armature: Option<String>,
+    /// Translation vector
+    #[serde(default)]
+    pub translate: [f32; 3],
+    /// Scale vector
+    #[serde(default = "ModelSetup::default_scale")]
+    pub scale: [f32; 3],
+    /// Rotation angles around X, Y and Z axes in degrees
+    #[serde(default)]
+    pub rotate: [f32; 3],
+}
+
+impl ModelSetup {
+    fn default_scale() -> [f32; 3] {
+        [1.0, 1.0, 1.0]
+    }
+
+    /// Returns the model, resolving assets through [`Assets::find`]
+    pub fn model(&self, assets: &Assets) -> Model {
+        let [x, y, z] = self.rotate.map(f32::to_radians);
+        Model {
+            mesh: find_asset(assets, Some(&self.mesh)),
+            material: find_asset(assets, self.material.as_deref()),
+            armature: find_asset(assets, self.armature.as_deref()),
+            translate: Vec3::from(self.translate),
+            scale: Vec3::from(self.scale),
+            rotate: Quat::from_euler(EulerRot::XYZ, x, y, z),
+            ..Default::default()
+        }
+    }
+}
+
+/// Description of a [`Viewpoint`]
+#[derive(Debug, Clone, Deserialize)]
+pub struct CameraSetup {
+    /// Position of the camera
+    pub position: [f32; 3],
+    /// Point, the camera looks at
+    #[serde(default)]
+    pub target: [f32; 3],
+    /// Field of view in degrees
+    pub fov: Option<f32>,
+    /// Distance to the near plane
+    pub near: Option<f32>,
+    /// Distance to the far plane
+    pub far: Option<f32>,
+}
+
+impl CameraSetup {
+    /// Returns the viewpoint
+    pub fn viewpoint(&self) -> Viewpoint {
+        let lens = Lens::default();
+        let near = self.near.unwrap_or(lens.plane.start);
+        let far = self.far.unwrap_or(lens.plane.end);
+        Viewpoint {
+            lens: Lens::new(self.fov.map(f32::to_radians).unwrap_or(lens.fov), near..far),
+            position: Vec3::from(self.position),
+            target: Vec3::from(self.target),
+        }
+    }
+}
+
+/// Camera placement spawned from a scene
+///
+/// Default viewpoint looks at the origin along the Z axis
+pub struct Viewpoint {
+    /// Projection of the camera
+    pub lens: Lens,
+    /// Position of the camera
+    pub position: Vec3,
+    /// Point, the camera looks at
+    pub target: Vec3,
+}
+
+impl Viewpoint {
+    /// Returns view matrix
+    pub fn view(&self) -> Mat4 {
+        Camera::at(self.position.x, self.position.y, self.position.z).target(self.target)
+    }
+
+    /// Returns camera for the surface
+    pub fn camera(&self, surface_width: u32, surface_height: u32) -> Camera {
+        Camera::new(
+            self.lens.proj(surface_width, surface_height),
+            self.view(),
+            self.target,
+        )
+    }
+}
+
+impl Default for Viewpoint {
+    fn default() -> Self {
+        Self {
+            lens: Lens::default(),
+            position: Vec3::Z,
+            target: Vec3::ZERO,
+        }
+    }
+}
+
+/// Scene asset, loaded by [`SceneLoader`]
+///
+/// Assets of the scene are loaded together with it, entities are spawned by [`Scene::spawn`] or
+/// by [`SpawnScene`] task
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    /// Name of the scene
+    pub name: String,
+    /// Entities to be spawned
+    pub entities: Vec<EntitySetup>,
+}
+
+impl Asset for Scene {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Scene {
+    /// Spawns entities of the scene, returns their ids in order of the description
+    ///
+    /// Assets are found by their names, so they must be stored before the spawn
+    pub fn spawn(&self, world: &mut World, assets: &Assets) -> Vec<Id<Entity>> {
+        let ids = self
+            .entities
+            .iter()
+            .map(|setup| {
+                let mut entity = match setup.model.as_ref() {
+                    Some(model) => Entity::from(model.model(assets)),
+                    None => Entity::empty(),
+                };
+                if let Some(camera) = setup.camera.as_ref() {
+                    entity = entity.with(camera.viewpoint());
+                }
+                world
+                    .spawn(Some(entity))
+                    .next()
+                    .expect("Entity to be spawned")
+            })
+            .collect::<Vec<_>>();
+
+        let names = self
+            .entities
+            .iter()
+            .zip(ids.iter())
+            .filter_map(|(setup, id)| setup.name.as_deref().map(|name| (name, *id)))
+            .collect::<HashMap<_, _>>();
+
+        for (setup, id) in self.entities.iter().zip(ids.iter()) {
+            if let Some(parent) = setup.parent.as_deref() {
+                match names.get(parent) {
+                    Some(parent_id) if world.set_parent(id, parent_id) => {}
+                    _ => log::warn!("Scene {}: invalid parent `{}`", self.name, parent),
+                }
+            }
+        }
+        ids
+    }
+}
+
+/// Scene description file loader
+///
+/// Scene is described in TOML. Referenced resources are read by [`GltfLoader`] or
+/// [`ImageLoader`], their assets are added to the bundle together with the [`Scene`]. Entities
+/// describe models and cameras, the camera is applied by [`ViewpointCamera`] task. Light sources
+/// are not supported, lighting is defined by the renderer:
+///
+/// ```toml
+/// name = "garage"
+///
+/// [[resources]]
+/// path = "models/car.gltf"
+///
+/// [[materials]]
+/// name = "Red Material"
+/// albedo = [1.0, 0.0, 0.0]
+///
+/// [[meshes]]
+/// name = "Floor"
+/// shape = "cube"
+///
+/// [[entities]]
+/// name = "floor"
+/// model = { mesh = "Floor", material = "Red Material", scale = [10.0, 0.1, 10.0] }
+///
+/// [[entities]]
+/// parent = "floor"
+/// model = { mesh = "car::body::mesh", material = "car::body::material", rotate = [0.0, 90.0, 0.0] }
+///
+/// [[entities]]
+/// camera = { position = [0.0, 5.0, 10.0], fov = 60.0 }
+/// ```
+#[derive(Default)]
+pub struct SceneLoader;
+
+impl ResourceLoader for SceneLoader {
+    fn read(&self, path: &Path, targets: &HashSet<ResourceTarget>) -> ResourceBundle {
+        let mut bundle = targets
+            .iter()
+            .map(|target| (target.clone(), None))
+            .collect::<HashMap<_, _>>();
+
+        let setup = match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str::<SceneSetup>(&text)
+                .map_err(|err| log::error!("Could not parse scene file `{path:?}`: {err}"))
+                .ok(),
+            Err(err) => panic!("Could not open scene resource file ({path:?}): {err:?}"),
+        };
+
+        if let Some(setup) = setup {
+            let no_targets = targets.is_empty();
+            let directory = path.parent().unwrap_or_else(|| Path::new(""));
+            let name = setup.name.clone().unwrap_or_else(|| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .expect("Could not get file name from its path")
+                    .into()
+            });
+
+            let assets = Self::read_resources(directory, &setup.resources)
+                .into_iter()
+                .chain(Self::read_materials(&setup.materials))
+                .chain(Self::read_meshes(&setup.meshes))
+                .chain(Some(Box::new(Scene {
+                    name,
+                    entities: setup.entities,
+                }) as Box<dyn Asset>));
+
+            for asset in assets {
+                let target = ResourceTarget {
+                    type_id: asset.type_id(),
+                    name: asset.name().into(),
+                };
+                if no_targets || targets.contains(&target) {
+                    bundle.insert(target, Some(asset));
+                }
+            }
+        }
+
+        ResourceBundle {
+            resource: path.into(),
+            bundle,
+        }
+    }
+}
+
+impl SceneLoader {
+    fn read_resources(directory: &Path, resources: &[ResourceSetup]) -> Vec<Box<dyn Asset>> {
+        let mut result = Vec::new();
+        for resource in resources.iter() {
+            let path = directory.join(&resource.path);
+            let extension = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| extension.to_lowercase());
+            let bundle = match extension.as_deref() {
+                Some("gltf") | Some("glb") => GltfLoader.read(&path, &HashSet::new()),
+                _ if image::ImageFormat::from_path(&path).is_ok() => {
+                    ImageLoader.read(&path, &HashSet::new())
+                }
+                _ => {
+                    log::warn!("Scene resource `{path:?}` is of unsupported format");
+                    continue;
+                }
+            };
+            result.extend(bundle.bundle.into_values().flatten());
+        }
+        result
+    }
+
+    fn read_materials(materials: &[MaterialSetup]) -> Vec<Box<dyn Asset>> {
+        materials
+            .iter()
+            .map(|setup| {
+                let default = Material::default();
+                Box::new(Material {
+                    name: setup.name.clone(),
+                    albedo: setup
+                        .albedo
+                        .as_deref()
+                        .map(color_of)
+                        .unwrap_or(default.albedo),
+                    metallic_factor: setup.metallic_factor.unwrap_or(default.metallic_factor),
+                    roughness_factor: setup.roughness_factor.unwrap_or(default.roughness_factor),
+                    occlusion_factor: setup.occlusion_factor.unwrap_or(default.occlusion_factor),
+                    ..default
+                }) as Box<dyn Asset>
+            })
+            .collect()
+    }
+
+    fn read_meshes(meshes: &[MeshSetup]) -> Vec<Box<dyn Asset>> {
+        meshes
+            .iter()
+            .map(|setup| {
+                let name = setup.name.as_str();
+                let (u, v) = (setup.segments, setup.rings);
+                Box::new(match setup.shape {
+                    Shape::Cube => Mesh::cube(name),
+                    Shape::Sphere => Mesh::sphere(name, u, v),
+                    Shape::Hemisphere => Mesh::hemisphere(name, u, v),
+                    Shape::Cylinder => Mesh::cylinder(name, u, Some(v)),
+                    Shape::Cone => Mesh::cone(name, u),
+                }) as Box<dyn Asset>
+            })
+            .collect()
+    }
+}
+
+/// Output of [`SpawnScene`] task
+pub struct ScenesSpawned {
+    /// Ids of spawned entities by names of the scenes
+    pub entities: HashMap<String, Vec<Id<Entity>>>,
+}
+
+/// Task spawning scenes, stored by [`super::StoreAssets`]
+///
+/// Spawned scenes are removed from the assets
+#[derive(Default)]
+pub struct SpawnScene;
+
+impl Task for SpawnScene {
+    type Context = (Any<ResourceReport>, Mut<Assets>, Mut<World>);
+    type Output = ScenesSpawned;
+
+    fn run(&mut self, (report, mut assets, mut world): Self::Context) -> Self::Output {
+        let scene_type_id = std::any::TypeId::of::<Scene>();
+        let scenes = report
+            .report
+            .iter()
+            .filter(|(target, _)| target.type_id == scene_type_id)
+            .filter_map(|(_, id)| id.map(Id::<Scene>::from))
+            .filter_map(|id| assets.remove(id))
+            .collect::<Vec<_>>();
+        let entities = scenes
+            .into_iter()
+            .map(|scene| {
+                let ids = scene.spawn(&mut world, &assets);
+                (scene.name, ids)
+            })
+            .collect();
+        ScenesSpawned { entities }
+    }
+}
+
+/// Task providing [`Camera`] of the first found [`Viewpoint`] in the world
+///
+/// Default viewpoint is used, while there is none
+#[derive(Default)]
+pub struct ViewpointCamera;
+
+impl Task for ViewpointCamera {
+    type Context = (Any<Frame>, Ref<World>);
+    type Output = Camera;
+
+    fn run(&mut self, (frame, world): Self::Context) -> Self::Output {
+        let Extent2D { width, height } = frame.resolution;
+        match world.query::<(&Viewpoint,)>().next() {
+            Some((viewpoint,)) => viewpoint.camera(width, height),
+            None => Viewpoint::default().camera(width, height),
+        }
+    }
+}
+
+fn color_of(channels: &[f32]) -> Color<f32> {
+    match *channels {
+        [r, g, b] => Color::rgba(r, g, b, 1.0),
+        [r, g, b, a] => Color::rgba(r, g, b, a),
+        _ => {
+            log::warn!("Color must have 3 or 4 channels, got {}", channels.len());
+            Color::white()
+        }
+    }
+}
+
+fn find_asset<T: Asset>(assets: &Assets, name: Option<&str>) -> Id<T> {
+    let Some(name) = name else {
+        return Id::null();
+    };
+    assets.find(name).unwrap_or_else(|| {
+        log::warn!("Scene asset `{name}` was not found");
+        Id::null()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scene, SceneLoader, Viewpoint, ViewpointCamera};
+    use crate::headless::Headless;
+    use crate::loaders::{Assets, ResourceFile};
+    use crate::math::{Mat4, Vec3};
+    use crate::models::{Image, Material, Mesh};
+    use crate::tasks::{Any, OutputChannel, Task};
+    use crate::world::{Camera, Children, Lens, Parent, World};
+    use crate::{Id, Transform};
+
+    #[test]
+    fn can_load_and_spawn_scene() {
+        let directory = std::env::temp_dir().join(format!("dotrix-scene-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let manifest = env!("CARGO_MANIFEST_DIR");
+        std::fs::copy(
+            format!("{manifest}/resources/dotrix.png"),
+            directory.join("logo.png"),
+        )
+        .unwrap();
+        let path = directory.join("garage.toml");
+        std::fs::write(
+            &path,
+            format!(
+                r#"
+                [[resources]]
+                path = "logo.png"
+
+                [[resources]]
+                path = "{manifest}/resources/models/gift.gltf"
+
+                [[materials]]
+                name = "Red Material"
+                albedo = [1.0, 0.0, 0.0]
+
+                [[meshes]]
+                name = "Cube"
+                shape = "cube"
+
+                [[entities]]
+                name = "floor"
+                model = {{ mesh = "Cube", material = "Red Material", translate = [0.0, -1.0, 0.0] }}
+
+                [[entities]]
+                name = "box"
+                parent = "floor"
+                model = {{ mesh = "Cube", material = "Missing Material", rotate = [0.0, 90.0, 0.0] }}
+
+                [[entities]]
+                camera = {{ position = [0.0, 5.0, 10.0], fov = 60.0 }}
+                "#
+            ),
+        )
+        .unwrap();
+
+        let mut bundle = ResourceFile::new(&path, SceneLoader).read();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let scene = bundle
+            .extract::<Scene>("garage")
+            .expect("Scene to be loaded");
+        assert_eq!(scene.entities.len(), 3);
+        assert!(bundle.extract::<Image>("logo").is_some());
+
+        let mut assets = Assets::new();
+        for asset in bundle.bundle.into_values().flatten() {
+            assets.store(asset);
+        }
+        assert!(assets.find::<Mesh>("gift::mesh").is_some());
+        let cube = assets.find::<Mesh>("Cube").unwrap();
+        let red = assets.find::<Material>("Red Material").unwrap();
+
+        let mut world = World::new();
+        let ids = scene.spawn(&mut world, &assets);
+        assert_eq!(ids.len(), 3);
+
+        let (mesh, material, transform, children) = world
+            .query::<(&Id<Mesh>, &Id<Material>, &Transform, &Children)>()
+            .next()
+            .unwrap();
+        assert_eq!((*mesh, *material), (cube, red));
+        assert_eq!(transform.model.translate.y, -1.0);
+        assert!(children.contains(&ids[1]));
+
+        let (material, transform, parent) = world
+            .query::<(&Id<Material>, &Transform, &Parent)>()
+            .next()
+            .unwrap();
+        assert!(material.is_null());
+        assert!((transform.model.rotate.y - std::f32::consts::FRAC_PI_4.sin()).abs() < 1e-6);
+        assert_eq!(parent.id(), ids[0]);
+
+        let (viewpoint,) = world.query::<(&Viewpoint,)>().next().unwrap();
+        assert_eq!(viewpoint.lens.fov, 60.0_f32.to_radians());
+        assert_eq!(viewpoint.position.z, 10.0);
+    }
+
+    struct Report;
+
+    impl Task for Report {
+        type Context = (Any<Camera>,);
+        type Output = (Mat4, Mat4);
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (camera,): Self::Context) -> Self::Output {
+            (camera.proj, camera.view)
+        }
+    }
+
+    #[test]
+    fn can_apply_viewpoint_to_camera() {
+        let viewpoint = Viewpoint {
+            lens: Lens::new(1.0, 0.5..100.0),
+            position: Vec3::new(0.0, 5.0, 10.0),
+            target: Vec3::new(0.0, 1.0, 0.0),
+        };
+        let expected = viewpoint.camera(800, 600);
+        let mut world = World::new();
+        world.spawn(Some((viewpoint,))).count();
+
+        let headless = Headless::<(Mat4, Mat4)>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_world(world);
+            scheduler.add_task(ViewpointCamera);
+            scheduler.add_task(Report);
+        }
+        assert_eq!(headless.step(), (expected.proj, expected.view));
+    }
+}
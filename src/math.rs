@@ -1,7 +1,7 @@
 //! Dotrix wrapper around glam
 
 pub use glam::{
-    IVec2 as Vec2i, IVec3 as Vec3i, IVec4 as Vec4i, Mat3, Mat4, Quat, UVec2 as Vec2u,
+    EulerRot, IVec2 as Vec2i, IVec3 as Vec3i, IVec4 as Vec4i, Mat3, Mat4, Quat, UVec2 as Vec2u,
     UVec3 as Vec3u, UVec4 as Vec4u, Vec2, Vec3, Vec4,
 };
 
@@ -22,7 +22,10 @@ mod renderer;
 pub use renderer::{RenderModels, RenderModelsSetup};
 
 mod transforms;
-pub use transforms::{Transform, Transform3D, TransformBuilder};
+pub use transforms::{
+    GlobalTransform, PropagateTransforms, Transform, Transform3D, TransformBuilder,
+    TransformsPropagated,
+};
 
 mod vertices;
 pub use vertices::{
@@ -48,14 +51,13 @@ pub struct Model {
 impl From<Model> for Entity {
     fn from(model: Model) -> Self {
         let animation = model.animation;
+        let transform = Transform3D::new(model.translate, model.rotate, model.scale);
         let mut entity = Entity::new((
             model.mesh,
             model.material,
             model.armature,
-            Transform::new(
-                Transform3D::new(model.translate, model.rotate, model.scale),
-                model.pose,
-            ),
+            GlobalTransform::from(transform.matrix()),
+            Transform::new(transform, model.pose),
         ));
 
         if let Some(player) = animation {
@@ -12,8 +12,8 @@ use crate::{Any, Asset, Display, Extent2D, Frame, Gpu, Image, Ref, Task};
 
 use super::materials::MaterialUniform;
 use super::{
-    Armature, Material, Mesh, Transform, VertexBufferLayout, VertexNormal, VertexPosition,
-    VertexTexture,
+    Armature, GlobalTransform, Material, Mesh, Transform, VertexBufferLayout, VertexNormal,
+    VertexPosition, VertexTexture,
 };
 
 #[derive(Clone, Copy)]
@@ -670,12 +670,13 @@ impl RenderModels {
                 .map_and_write_to_device_memory(&self.gpu, 0, &globals_uniform);
         }
 
-        for (_entity_id, mesh_id, material_id, _armature_id, transform) in world.query::<(
+        for (_entity_id, mesh_id, material_id, _armature_id, transform, global) in world.query::<(
             &Id<Entity>,
             &Id<Mesh>,
             &Id<Material>,
-            &Id<Armature>,
+            Option<&Id<Armature>>,
             &Transform,
+            Option<&GlobalTransform>,
         )>() {
             let material_index = self.register_material(*material_id, assets);
             if material_index.is_none() {
@@ -697,7 +698,10 @@ impl RenderModels {
                 };
                 let transform_index = self.transform_buffer_data.len() as u32;
                 self.transform_buffer_data.push(TransformUniform {
-                    transform: transform.model.matrix().to_cols_array_2d(),
+                    transform: global
+                        .map(|global| global.matrix)
+                        .unwrap_or_else(|| transform.model.matrix())
+                        .to_cols_array_2d(),
                 });
                 let mut joint_index = 0; // NOTE: real joint can never have a 0 index
                 if mesh_layout.has_skin && !transform.armature.is_empty() {
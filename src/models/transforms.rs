@@ -1,5 +1,10 @@
 //! Transformation structure and builder
+use std::collections::{HashMap, HashSet};
+
 use crate::math::{Mat4, Quat, Vec3};
+use crate::tasks::{Mut, Order, Task};
+use crate::utils::Id;
+use crate::world::{Added, ApplyCommands, Changed, Children, Entity, Parent, Without, World};
 
 /// Agregator for skin transformations
 #[derive(Default)]
@@ -161,3 +166,336 @@ impl Default for Transform3D {
         }
     }
 }
+
+/// World-space transformation of an entity
+///
+/// Calculated by [`PropagateTransforms`] from the [`Transform`] of the entity and its parents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalTransform {
+    /// Transformation matrix
+    pub matrix: Mat4,
+}
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self {
+            matrix: Mat4::IDENTITY,
+        }
+    }
+}
+
+impl From<Mat4> for GlobalTransform {
+    fn from(matrix: Mat4) -> Self {
+        Self { matrix }
+    }
+}
+
+/// Output of [`PropagateTransforms`] task
+pub struct TransformsPropagated;
+
+/// Task propagating transformations down the hierarchy of entities into [`GlobalTransform`]
+///
+/// Entities with [`Transform`], but without [`GlobalTransform`], receive the component. Parents
+/// without [`Transform`] don't affect the result. Tasks, that need up to date global matrices,
+/// depend on `Any<TransformsPropagated>`.
+///
+/// After the first run only subtrees of entities with changed [`Transform`] or [`Parent`] are
+/// recalculated, so tasks changing them must be executed before this one in the loop, see
+/// [`crate::tasks::Scheduler::add_world`]. [`GlobalTransform`] is written only if its matrix
+/// differs.
+#[derive(Default)]
+pub struct PropagateTransforms {
+    /// Parents of entities as of the last run, used to find detached entities
+    parents: Option<HashMap<Id<Entity>, Id<Entity>>>,
+}
+
+impl Task for PropagateTransforms {
+    type Context = (Mut<World>,);
+    type Output = TransformsPropagated;
+
+    fn after(&self) -> Vec<Order> {
+        vec![Order::task::<ApplyCommands>()]
+    }
+
+    fn before(&self) -> Vec<Order> {
+        vec![Order::task::<super::RenderModels>()]
+    }
+
+    fn run(&mut self, (mut world,): Self::Context) -> Self::Output {
+        let missing = world
+            .query::<(&Id<Entity>, Without<GlobalTransform>, &Transform)>()
+            .map(|(id, _, transform)| (*id, GlobalTransform::from(transform.model.matrix())))
+            .collect::<Vec<_>>();
+        world.insert_component_batch(missing);
+
+        let full = self.parents.is_none();
+        let parents = self.parents.get_or_insert_with(HashMap::new);
+        let mut changed = Vec::new();
+        if full {
+            // changes made before the first run are not tracked
+            for (id, parent) in world.query::<(&Id<Entity>, Option<&Parent>)>() {
+                if let Some(parent) = parent {
+                    parents.insert(*id, parent.id());
+                }
+                changed.push(*id);
+            }
+        } else {
+            changed.extend(
+                world
+                    .query::<(&Id<Entity>, Changed<Transform>)>()
+                    .map(|(id, _)| *id),
+            );
+            changed.extend(
+                world
+                    .query::<(&Id<Entity>, Added<GlobalTransform>)>()
+                    .map(|(id, _)| *id),
+            );
+            for (id, parent, _) in world.query::<(&Id<Entity>, &Parent, Changed<Parent>)>() {
+                parents.insert(*id, parent.id());
+                changed.push(*id);
+            }
+        }
+        // removal of the parent is not tracked, so detached entities are found by the cache
+        if world.query::<(&Parent,)>().count() != parents.len() {
+            parents.retain(|id, _| {
+                if world.component::<Parent>(id).is_some() {
+                    return true;
+                }
+                changed.push(*id);
+                false
+            });
+        }
+
+        let mut subtree = HashSet::new();
+        while let Some(id) = changed.pop() {
+            if subtree.insert(id) {
+                if let Some(children) = world.component::<Children>(&id) {
+                    changed.extend(children.iter().copied());
+                }
+            }
+        }
+
+        let mut globals = HashMap::with_capacity(subtree.len());
+        let updates = subtree
+            .iter()
+            .filter_map(|id| {
+                let global = world.component::<GlobalTransform>(id)?;
+                let matrix = resolve(id, &world, &mut globals);
+                (global.matrix != matrix).then(|| (*id, GlobalTransform::from(matrix)))
+            })
+            .collect::<Vec<_>>();
+        world.insert_component_batch(updates);
+        TransformsPropagated
+    }
+}
+
+/// Calculates global matrix of the entity, caching matrices of its ancestors
+///
+/// The world must be borrowed mutably by the caller, so no query holds the components
+fn resolve(id: &Id<Entity>, world: &World, globals: &mut HashMap<Id<Entity>, Mat4>) -> Mat4 {
+    let mut chain = Vec::new();
+    let mut base = Mat4::IDENTITY;
+    let mut current = Some(*id);
+    while let Some(entity) = current {
+        if let Some(global) = globals.get(&entity) {
+            base = *global;
+            break;
+        }
+        let local = world
+            .component::<Transform>(&entity)
+            .map(|transform| transform.model.matrix())
+            .unwrap_or(Mat4::IDENTITY);
+        chain.push((entity, local));
+        current = world.component::<Parent>(&entity).map(Parent::id);
+    }
+    for (entity, local) in chain.into_iter().rev() {
+        base *= local;
+        globals.insert(entity, base);
+    }
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        GlobalTransform, PropagateTransforms, Transform, Transform3D, TransformsPropagated,
+    };
+    use crate::headless::Headless;
+    use crate::math::{Mat4, Vec3};
+    use crate::tasks::{Any, Mut, Order, OutputChannel, Ref, Task};
+    use crate::utils::Id;
+    use crate::world::{Changed, Entity, World};
+
+    struct Name(&'static str);
+
+    fn spawn(world: &mut World, name: &'static str, translate: Vec3) -> Id<Entity> {
+        let transform = Transform::new(Transform3D::from_translation(translate), Vec::new());
+        world.spawn(Some((Name(name), transform))).next().unwrap()
+    }
+
+    /// Counts loops itself, so it is ready together with [`PropagateTransforms`]
+    struct Move(u32);
+
+    impl Task for Move {
+        type Context = (Mut<World>,);
+        type Output = ();
+
+        fn before(&self) -> Vec<Order> {
+            vec![Order::task::<PropagateTransforms>()]
+        }
+
+        fn run(&mut self, (world,): Self::Context) -> Self::Output {
+            self.0 += 1;
+            if self.0 == 2 {
+                for (name, transform) in world.query::<(&Name, &mut Transform)>() {
+                    if name.0 == "car" {
+                        transform.model.translate.x = 10.0;
+                    }
+                }
+            }
+        }
+    }
+
+    struct Positions;
+
+    impl Task for Positions {
+        type Context = (Any<TransformsPropagated>, Ref<World>);
+        type Output = Vec<(&'static str, Vec3)>;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_propagated, world): Self::Context) -> Self::Output {
+            let mut positions = world
+                .query::<(&Name, &GlobalTransform)>()
+                .map(|(name, global)| (name.0, global.matrix.transform_point3(Vec3::ZERO)))
+                .collect::<Vec<_>>();
+            positions.sort_by_key(|(name, _)| *name);
+            positions
+        }
+    }
+
+    #[test]
+    fn can_propagate_transforms() {
+        let mut world = World::new();
+        let car = spawn(&mut world, "car", Vec3::new(1.0, 0.0, 0.0));
+        let wheel = spawn(&mut world, "wheel", Vec3::new(0.0, 2.0, 0.0));
+        let bolt = spawn(&mut world, "bolt", Vec3::new(0.0, 0.0, 3.0));
+        // parents without transform don't affect children
+        let group = world.spawn(Some((Name("group"),))).next().unwrap();
+        let tree = spawn(&mut world, "tree", Vec3::new(5.0, 0.0, 0.0));
+        world
+            .spawn(Some((Name("rock"), GlobalTransform::from(Mat4::IDENTITY))))
+            .count();
+        assert!(world.set_parent(&wheel, &car));
+        assert!(world.set_parent(&bolt, &wheel));
+        assert!(world.set_parent(&tree, &group));
+
+        let headless = Headless::<Vec<(&'static str, Vec3)>>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_world(world);
+            scheduler.add_task(Move(0));
+            scheduler.add_task(PropagateTransforms::default());
+            scheduler.add_task(Positions);
+        }
+        assert_eq!(
+            headless.step(),
+            [
+                ("bolt", Vec3::new(1.0, 2.0, 3.0)),
+                ("car", Vec3::new(1.0, 0.0, 0.0)),
+                ("rock", Vec3::ZERO),
+                ("tree", Vec3::new(5.0, 0.0, 0.0)),
+                ("wheel", Vec3::new(1.0, 2.0, 0.0)),
+            ]
+        );
+        let positions = headless.step();
+        assert_eq!(positions[0], ("bolt", Vec3::new(10.0, 2.0, 3.0)));
+        assert_eq!(positions[4], ("wheel", Vec3::new(10.0, 2.0, 0.0)));
+    }
+
+    struct Edit {
+        step: u32,
+        wheel: Id<Entity>,
+        bolt: Id<Entity>,
+    }
+
+    impl Task for Edit {
+        type Context = (Mut<World>,);
+        type Output = ();
+
+        fn before(&self) -> Vec<Order> {
+            vec![Order::task::<PropagateTransforms>()]
+        }
+
+        fn run(&mut self, (mut world,): Self::Context) -> Self::Output {
+            self.step += 1;
+            match self.step {
+                3 | 5 => {
+                    for (id, transform) in world.query::<(&Id<Entity>, &mut Transform)>() {
+                        if *id == self.wheel {
+                            transform.model.translate.x = 1.0;
+                        }
+                    }
+                }
+                4 => {
+                    world.remove_parent(&self.bolt);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    struct Updated;
+
+    impl Task for Updated {
+        type Context = (Any<TransformsPropagated>, Ref<World>);
+        type Output = Vec<&'static str>;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_propagated, world): Self::Context) -> Self::Output {
+            let mut names = world
+                .query::<(&Name, Changed<GlobalTransform>)>()
+                .map(|(name, _)| name.0)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        }
+    }
+
+    #[test]
+    fn propagates_only_changed_subtrees() {
+        let mut world = World::new();
+        let car = spawn(&mut world, "car", Vec3::new(1.0, 0.0, 0.0));
+        let wheel = spawn(&mut world, "wheel", Vec3::new(0.0, 2.0, 0.0));
+        let bolt = spawn(&mut world, "bolt", Vec3::new(0.0, 0.0, 3.0));
+        spawn(&mut world, "tree", Vec3::new(5.0, 0.0, 0.0));
+        assert!(world.set_parent(&wheel, &car));
+        assert!(world.set_parent(&bolt, &wheel));
+
+        let headless = Headless::<Vec<&'static str>>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_world(world);
+            scheduler.add_task(Edit {
+                step: 0,
+                wheel,
+                bolt,
+            });
+            scheduler.add_task(PropagateTransforms::default());
+            scheduler.add_task(Updated);
+        }
+        assert_eq!(headless.step(), ["bolt", "car", "tree", "wheel"]);
+        assert_eq!(headless.step(), Vec::<&str>::new());
+        // children of the moved entity are updated
+        assert_eq!(headless.step(), ["bolt", "wheel"]);
+        // detached entity becomes a root
+        assert_eq!(headless.step(), ["bolt"]);
+        // unchanged matrices are not written
+        assert_eq!(headless.step(), Vec::<&str>::new());
+    }
+}
@@ -1,18 +1,30 @@
 mod context;
+mod errors;
+mod events;
+mod executor;
+mod graph;
+mod profiler;
 mod scheduler;
 mod task;
 mod worker;
 
 use std::sync::{mpsc, Arc, Mutex, MutexGuard};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::log;
 use crate::utils::Id;
+use crate::world::World;
 
 use context::Context;
 
-pub use context::{All, Any, Mut, Ref, State, Take};
-pub use task::{Output, OutputChannel, Task};
+pub use context::{All, Any, Mut, OnEnter, OnExit, Ref, State, Take, Try};
+pub use errors::{Abandoned, Errors, Failure, FailurePolicy, TaskError};
+pub use events::{EventReader, Events};
+pub use graph::{Access, Dependency, Error as GraphError, Graph, Node, OutputNode, Selection};
+pub use profiler::{Profiler, Record as ProfilerRecord};
+pub use scheduler::{FixedTick, FixedUpdate};
+pub use task::{AsyncTask, Condition, FallibleTask, Order, Output, OutputChannel, Task};
 
 /// Dotrix Task Manager
 ///
@@ -20,6 +32,8 @@ pub use task::{Output, OutputChannel, Task};
 pub struct TaskManager {
     /// Scheduler thread handle
     scheduler: Option<thread::JoinHandle<()>>,
+    /// Executor thread handle
+    executor: Option<thread::JoinHandle<()>>,
     /// List of workers
     workers: Vec<thread::JoinHandle<()>>,
     /// Receive reports from scheduler
@@ -45,6 +59,59 @@ impl<'a> Scheduler<'a> {
         id
     }
 
+    /// Add async task to the scheduler
+    pub fn add_async_task<T: task::AsyncTask>(&self, task: T) -> Id<task::Slot> {
+        let id = Id::new();
+        let task = task::AsyncTask::boxify(task, id);
+        self.guard
+            .send(scheduler::Message::Schedule(task))
+            .expect("Message to be sent to Scheduler");
+        id
+    }
+
+    /// Add fallible task to the scheduler
+    pub fn add_fallible_task<T: task::FallibleTask>(&self, task: T) -> Id<task::Slot> {
+        let id = Id::new();
+        let task = task::FallibleTask::boxify(task, id);
+        self.guard
+            .send(scheduler::Message::Schedule(task))
+            .expect("Message to be sent to Scheduler");
+        id
+    }
+
+    /// Remove task from the scheduler
+    ///
+    /// Changes of tasks requested during a loop are applied, when the loop is executed
+    pub fn remove_task(&self, id: Id<task::Slot>) {
+        self.guard
+            .send(scheduler::Message::RemoveTask(id))
+            .expect("Message to be sent to Scheduler");
+    }
+
+    /// Pause task, so it won't be executed until resumed
+    pub fn pause_task(&self, id: Id<task::Slot>) {
+        self.guard
+            .send(scheduler::Message::PauseTask(id))
+            .expect("Message to be sent to Scheduler");
+    }
+
+    /// Resume paused task
+    pub fn resume_task(&self, id: Id<task::Slot>) {
+        self.guard
+            .send(scheduler::Message::ResumeTask(id))
+            .expect("Message to be sent to Scheduler");
+    }
+
+    /// Replace task with a new one, keeping the same `Id`
+    ///
+    /// Changes of tasks requested during a loop are applied, when the loop is executed
+    pub fn replace_task<T: task::Task>(&self, id: Id<task::Slot>, task: T) {
+        let task = task.boxify(id);
+        self.guard
+            .send(scheduler::Message::ReplaceTask(task))
+            .expect("Message to be sent to Scheduler");
+    }
+
     /// Add global data to the context
     pub fn add_context<T: context::Context + Send>(&self, ctx: T) {
         self.guard
@@ -55,16 +122,74 @@ impl<'a> Scheduler<'a> {
             .expect("Message to be sent to Scheduler");
     }
 
-    /// Set state
-    pub fn push_state<T: context::Context + Send>(&self, state: T) {
+    /// Register output data type
+    ///
+    /// Registered outputs are kept until taken, so they can be provided by any number of tasks
+    pub fn register<T: context::Context + Send>(&self, providers: usize) {
         self.guard
-            .send(scheduler::Message::PushState(
+            .send(scheduler::Message::Register(
                 std::any::TypeId::of::<T>(),
                 std::any::type_name::<T>().into(),
-                Box::new(state),
+                providers,
+            ))
+            .expect("Message to be sent to Scheduler");
+    }
+
+    /// Add event channel to the global context
+    ///
+    /// The channel is updated by the scheduler at the beginning of every loop
+    pub fn add_events<T: Send + 'static>(&self, events: Events<T>) {
+        self.guard
+            .send(scheduler::Message::StoreUpdated(
+                std::any::TypeId::of::<Events<T>>(),
+                Box::new(events),
+                events::update::<T>,
+            ))
+            .expect("Message to be sent to Scheduler");
+    }
+
+    /// Add world to the global context
+    ///
+    /// Change trackers of the world are reset by the scheduler at the beginning of every loop,
+    /// so [`crate::world::Added`] and [`crate::world::Changed`] filters match changes made since then
+    pub fn add_world(&self, world: World) {
+        self.guard
+            .send(scheduler::Message::StoreUpdated(
+                std::any::TypeId::of::<World>(),
+                Box::new(world),
+                clear_trackers,
             ))
             .expect("Message to be sent to Scheduler");
     }
+
+    /// Enables fixed-rate ticks with specified time step
+    ///
+    /// Scheduler provides [`FixedTick`] zero or several times per loop and [`FixedUpdate`] once
+    /// per loop. Panics, if the time step is zero.
+    pub fn set_fixed_timestep(&self, delta: std::time::Duration) {
+        assert!(!delta.is_zero(), "Fixed time step must not be zero");
+        self.guard
+            .send(scheduler::Message::FixedTimestep(delta))
+            .expect("Message to be sent to Scheduler");
+    }
+
+    /// Enables profiling of tasks execution
+    ///
+    /// The profiler is also stored in the global context
+    pub fn add_profiler(&self, profiler: Profiler) {
+        self.guard
+            .send(scheduler::Message::Profile(profiler))
+            .expect("Message to be sent to Scheduler");
+    }
+
+    /// Set state
+    pub fn push_state<T: context::Context + Send>(&self, state: T) {
+        self.guard
+            .send(scheduler::Message::PushState(context::StateSlot::from(
+                state,
+            )))
+            .expect("Message to be sent to Scheduler");
+    }
 }
 
 impl TaskManager {
@@ -75,6 +200,7 @@ impl TaskManager {
         let (scheduler_tx, scheduler_rx) = mpsc::channel();
         let (worker_tx, worker_rx) = mpsc::channel();
         let (control_tx, control_rx) = mpsc::channel();
+        let (executor_tx, executor_rx) = futures::channel::mpsc::unbounded();
         let worker_rx = Arc::new(Mutex::new(worker_rx));
         let scheduler_tx = Arc::new(Mutex::new(scheduler_tx));
 
@@ -89,11 +215,19 @@ impl TaskManager {
             })
             .collect::<Vec<_>>();
 
-        let scheduler =
-            scheduler::spawn::<T>(Arc::clone(&context), scheduler_rx, worker_tx, control_tx);
+        let executor = executor::spawn(executor_rx, Arc::clone(&scheduler_tx));
+
+        let scheduler = scheduler::spawn::<T>(
+            Arc::clone(&context),
+            scheduler_rx,
+            worker_tx,
+            control_tx,
+            executor_tx,
+        );
 
         Self {
             scheduler: Some(scheduler),
+            executor: Some(executor),
             workers,
             control_rx,
             scheduler_tx,
@@ -160,54 +294,136 @@ impl TaskManager {
             .expect("Message to be sent to Scheduler");
     }
 
+    /// Returns snapshot of the tasks graph
+    pub fn graph(&self) -> Graph {
+        let (graph_tx, graph_rx) = mpsc::channel();
+        self.lock_scheduler_tx()
+            .send(scheduler::Message::Inspect(graph_tx))
+            .expect("Message to be sent to Scheduler");
+        graph_rx.recv().expect("Graph to be received")
+    }
+
     /// Executes tasks cycle
     pub fn run(&self) {
         self.provide(scheduler::Loop);
     }
 
+    /// Validates tasks graph of the current state
+    pub fn validate(&self) -> Result<(), Vec<graph::Error>> {
+        self.graph().validate()
+    }
+
+    /// Shuts the tasks execution down
+    ///
+    /// Scheduler starts no new loops, but executes ready tasks of the current one. Then it calls
+    /// [`Task::shutdown`] hooks, drops tasks and global contexts in reverse order of their
+    /// registration. Returns false, if threads were not joined in time and were detached.
+    /// Unresolved futures of async tasks are dropped.
+    pub fn shutdown(&mut self, timeout: Duration) -> bool {
+        let scheduler = match self.scheduler.take() {
+            Some(scheduler) => scheduler,
+            None => return true,
+        };
+        let deadline = Instant::now() + timeout;
+        self.lock_scheduler_tx()
+            .send(scheduler::Message::Shutdown(self.workers.len()))
+            .ok();
+
+        // executor stops, when scheduler drops its sender
+        let mut threads = std::iter::once(scheduler)
+            .chain(self.executor.take())
+            .chain(self.workers.drain(0..))
+            .collect::<Vec<_>>();
+        loop {
+            let (finished, pending): (Vec<_>, Vec<_>) =
+                threads.into_iter().partition(|thread| thread.is_finished());
+            for thread in finished.into_iter() {
+                thread.join().ok();
+            }
+            if pending.is_empty() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                for thread in pending.iter() {
+                    log::warn!(
+                        "{} was not stopped in time",
+                        thread.thread().name().unwrap_or("UNKNOWN")
+                    );
+                }
+                return false;
+            }
+            threads = pending;
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
     /// Waits until data of specified type provided
-    pub fn wait_for<T: std::any::Any>(&self) -> T {
+    ///
+    /// Returns an error, if the loop was abandoned due to failures of tasks, so the caller
+    /// decides whether to start a new one. Panics in debug builds, if the tasks graph is invalid,
+    /// and on failure of a task with [`FailurePolicy::Abort`]
+    pub fn wait_for<T: std::any::Any>(&self) -> Result<T, Abandoned> {
         loop {
-            let message = self.control_rx.recv().expect("Message to be received");
-            if let scheduler::Message::Provide(_type_id, data) = message {
-                if let Ok(downcasted_data) = data.downcast::<T>() {
-                    return *downcasted_data;
+            match self.control_rx.recv().expect("Message to be received") {
+                scheduler::Message::Provide(_type_id, data) => {
+                    if let Ok(downcasted_data) = data.downcast::<T>() {
+                        return Ok(*downcasted_data);
+                    }
                 }
+                scheduler::Message::Invalid(errors) => report_graph_errors(&errors),
+                scheduler::Message::Abandon(abandoned) => return Err(abandoned),
+                scheduler::Message::Abort(failure) => panic!("{}", failure),
+                _ => {}
             }
         }
     }
 
     /// Waits for a message from the control channel
-    pub fn wait_message(&self) -> Box<dyn std::any::Any> {
+    ///
+    /// Handles failures of tasks and invalid tasks graph the same way as [`Self::wait_for`]
+    pub fn wait_message(&self) -> Result<Box<dyn std::any::Any>, Abandoned> {
         loop {
-            let message = self.control_rx.recv().expect("Message to be received");
-            if let scheduler::Message::Provide(_type_id, data) = message {
-                return data;
+            match self.control_rx.recv().expect("Message to be received") {
+                scheduler::Message::Provide(_type_id, data) => return Ok(data),
+                scheduler::Message::Invalid(errors) => report_graph_errors(&errors),
+                scheduler::Message::Abandon(abandoned) => return Err(abandoned),
+                scheduler::Message::Abort(failure) => panic!("{}", failure),
+                _ => {}
             }
         }
     }
 }
 
-impl Drop for TaskManager {
-    fn drop(&mut self) {
-        let workers = self.workers.len();
-        // kill workers
-        self.lock_scheduler_tx()
-            .send(scheduler::Message::Kill(workers))
-            .expect("Message to be sent to Scheduler");
+/// Default time limit of the [`TaskManager`] shutdown
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
-        for worker in self.workers.drain(0..) {
-            worker.join().ok();
-        }
+/// Resets change trackers of the boxified world
+fn clear_trackers(world: &mut (dyn std::any::Any + Send)) {
+    if let Some(world) = world.downcast_mut::<World>() {
+        world.clear_trackers();
+    }
+}
 
-        // kill scheduler
-        self.lock_scheduler_tx()
-            .send(scheduler::Message::Kill(0))
-            .expect("Message to be sent to Scheduler");
+/// Logs errors of the tasks graph and panics in debug builds
+fn report_graph_errors(errors: &[graph::Error]) {
+    for error in errors.iter() {
+        log::error!("{}", error);
+    }
+    if cfg!(debug_assertions) {
+        panic!(
+            "Tasks graph is invalid: {}",
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+    }
+}
 
-        if let Some(scheduler) = self.scheduler.take() {
-            scheduler.join().ok();
-        }
+impl Drop for TaskManager {
+    fn drop(&mut self) {
+        self.shutdown(SHUTDOWN_TIMEOUT);
     }
 }
 
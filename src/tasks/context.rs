@@ -1,7 +1,9 @@
 use std::any::TypeId;
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::marker::PhantomData;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::sync::{Arc, Mutex};
@@ -25,8 +27,13 @@ pub struct OutputSlot {
     providers: usize,
     /// Protected cells keep data if Some() on reset
     protected: bool,
+    /// Number of provisions made by the scheduler itself on every loop
+    scheduled: Option<usize>,
 }
 
+/// Constructor of a state transition event
+type Transition = fn() -> (TypeId, Box<dyn std::any::Any + Send + 'static>);
+
 /// Memory slot for a state
 pub struct StateSlot {
     /// State type Id
@@ -35,16 +42,31 @@ pub struct StateSlot {
     pub name: String,
     /// Boxified state
     pub data: UnsafeCell<Box<dyn std::any::Any + Send + 'static>>,
+    /// Constructor of the event of entering the state
+    on_enter: Transition,
+    /// Constructor of the event of exiting the state
+    on_exit: Transition,
 }
 
 ///  Context Manager
 pub struct Manager {
     globals: HashMap<TypeId, GlobalSlot>,
+    /// Types of global contexts in order of registration
+    globals_order: Vec<TypeId>,
     outputs: HashMap<TypeId, OutputSlot>,
     states_stack: Vec<StateSlot>,
     states_changes: Arc<Mutex<VecDeque<StateChangeType>>>,
+    /// Types of transition events, selected by tasks
+    transitions: HashSet<TypeId>,
+    /// Transition events to be provided in the next loop
+    transition_events: Vec<(TypeId, Box<dyn std::any::Any + Send + 'static>)>,
+    /// Global contexts, updated on every loop
+    updates: Vec<(TypeId, Update)>,
 }
 
+/// Function updating boxified global context at the beginning of every loop
+pub type Update = fn(&mut (dyn std::any::Any + Send));
+
 impl GlobalSlot {
     fn new<T>(context: T) -> Self
     where
@@ -71,6 +93,7 @@ impl From<&scheduler::Task> for OutputSlot {
             instances: vec![],
             providers: 0,
             protected: false,
+            scheduled: None,
         }
     }
 }
@@ -89,9 +112,13 @@ impl Manager {
     pub fn new() -> Self {
         Self {
             globals: HashMap::new(),
+            globals_order: Vec::new(),
             outputs: HashMap::new(),
             states_stack: vec![StateSlot::from(())],
             states_changes: Arc::new(Mutex::new(VecDeque::with_capacity(4))),
+            transitions: HashSet::new(),
+            transition_events: Vec::new(),
+            updates: Vec::new(),
         }
     }
 
@@ -225,8 +252,14 @@ impl Manager {
 
     /// Stores global context by type
     pub fn store_as<T: std::any::Any + Send + 'static>(&mut self, context: T) {
-        self.globals
-            .insert(std::any::TypeId::of::<T>(), GlobalSlot::new(context));
+        let type_id = std::any::TypeId::of::<T>();
+        if self
+            .globals
+            .insert(type_id, GlobalSlot::new(context))
+            .is_none()
+        {
+            self.globals_order.push(type_id);
+        }
     }
 
     /// Stores boxed global context
@@ -235,22 +268,65 @@ impl Manager {
         type_id: TypeId,
         context: Box<dyn std::any::Any + Send + 'static>,
     ) {
-        self.globals.insert(type_id, GlobalSlot::from(context));
+        if self
+            .globals
+            .insert(type_id, GlobalSlot::from(context))
+            .is_none()
+        {
+            self.globals_order.push(type_id);
+        }
+    }
+
+    /// Stores boxed global context and registers its update on every loop
+    pub fn store_updated(
+        &mut self,
+        type_id: TypeId,
+        context: Box<dyn std::any::Any + Send + 'static>,
+        update: Update,
+    ) {
+        self.store_boxed(type_id, context);
+        self.updates.retain(|(id, _)| *id != type_id);
+        self.updates.push((type_id, update));
+    }
+
+    /// Updates global contexts, registered for updates on every loop
+    pub fn update_globals(&mut self) {
+        for (type_id, update) in self.updates.iter() {
+            if let Some(slot) = self.globals.get_mut(type_id) {
+                update(slot.data.get_mut().as_mut());
+            }
+        }
     }
 
     /// Removes global context by type
     pub fn remove_global<T: std::any::Any + Send + 'static>(&mut self) -> Option<T> {
+        let type_id = std::any::TypeId::of::<T>();
+        self.globals_order.retain(|id| *id != type_id);
         self.globals
-            .remove(&std::any::TypeId::of::<T>())
+            .remove(&type_id)
             .and_then(|slot| slot.data.into_inner().downcast::<T>().ok())
             .map(|boxed| *boxed)
     }
 
     /// Discards global context by type_id
     pub fn discard(&mut self, type_id: TypeId) {
+        self.globals_order.retain(|id| *id != type_id);
         self.globals.remove(&type_id);
     }
 
+    /// Drops outputs, states and then global contexts in reverse order of registration
+    pub fn shutdown(&mut self) {
+        self.outputs.clear();
+        self.transition_events.clear();
+        while self.states_stack.len() > 1 {
+            self.states_stack.pop();
+        }
+        while let Some(type_id) = self.globals_order.pop() {
+            log::debug!("shutdown(context: {:?})", type_id);
+            self.globals.remove(&type_id);
+        }
+    }
+
     /// Register dependecy data
     pub fn register(&mut self, type_id: TypeId, name: String, providers: usize, protected: bool) {
         self.outputs.entry(type_id).or_insert(OutputSlot {
@@ -282,6 +358,57 @@ impl Manager {
         );
     }
 
+    /// Decrements number of providers of the output for the rest of the loop
+    ///
+    /// Used, when a provider has failed, so dependent tasks don't wait for its output
+    pub fn withdraw_provider(&mut self, type_id: TypeId) {
+        if let Some(slot) = self.outputs.get_mut(&type_id) {
+            slot.providers = slot.providers.saturating_sub(1);
+        }
+    }
+
+    /// Sets number of provisions made by the scheduler itself on every loop
+    pub fn set_scheduled_provisions(&mut self, type_id: TypeId, provisions: usize) {
+        if let Some(slot) = self.outputs.get_mut(&type_id) {
+            slot.scheduled = Some(provisions);
+        }
+    }
+
+    /// Returns number of provisions made by the scheduler itself, if the output is provided by it
+    pub fn scheduled_provisions(&self, type_id: &TypeId) -> Option<usize> {
+        self.outputs.get(type_id).and_then(|slot| slot.scheduled)
+    }
+
+    /// Returns number of providers of the output by type id
+    pub fn output_providers(&self, type_id: &TypeId) -> Option<usize> {
+        self.outputs.get(type_id).map(|slot| slot.providers)
+    }
+
+    /// Returns true if output is provided outside of tasks: registered or scheduled
+    pub fn is_external_output(&self, type_id: &TypeId) -> bool {
+        self.outputs
+            .get(type_id)
+            .map(|slot| slot.protected || slot.scheduled.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Returns true if output of the type was provided in the current loop
+    pub fn is_provided(&self, type_id: &TypeId) -> bool {
+        self.outputs
+            .get(type_id)
+            .map(|slot| !slot.instances.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Returns global context referrence by type
+    ///
+    /// Caller must ensure, that the type is not locked for writing
+    pub unsafe fn global<T: Context>(&self) -> Option<&T> {
+        self.globals
+            .get(&TypeId::of::<T>())
+            .and_then(|slot| (*slot.data.get()).downcast_ref::<T>())
+    }
+
     /// Returns name of output by types id
     pub fn output_name(&self, type_id: &TypeId) -> Option<&str> {
         self.outputs.get(type_id).map(|slot| slot.name.as_str())
@@ -308,9 +435,18 @@ impl Manager {
     /// Matches dependencies with provided context
     pub fn match_dependencies(&self, dependencies: &Dependencies) -> Option<Dependencies> {
         let mut result = dependencies.clone();
+        let loop_type_id = TypeId::of::<scheduler::Loop>();
+        // task with other `Any` dependencies runs once per their provision, not once per loop
+        let repeatable = dependencies.data.iter().any(|(type_id, dependency)| {
+            *type_id != loop_type_id && matches!(dependency, DependencyType::Any(_))
+        });
         for (type_id, dependency) in dependencies.data.iter() {
             let dependency_output = match self.outputs.get(type_id) {
                 Some(dependency) => dependency,
+                None if matches!(dependency, DependencyType::Try(_, _)) => {
+                    // nothing could be provided
+                    continue;
+                }
                 None => {
                     return None;
                 }
@@ -323,6 +459,8 @@ impl Manager {
                             .data
                             .insert(*type_id, DependencyType::Any(*index + 1));
                         continue;
+                    } else if repeatable && *type_id == loop_type_id && instances_len > 0 {
+                        continue;
                     } else {
                         return None;
                     }
@@ -343,6 +481,20 @@ impl Manager {
                         return None;
                     }
                 }
+                DependencyType::Try(index, _) => {
+                    // like `All`, waits for scheduled providers to report or to be withdrawn
+                    if instances_len < dependency_output.providers {
+                        return None;
+                    }
+                    // provisions taken by other tasks are skipped
+                    let selected = (*index..instances_len)
+                        .find(|i| unsafe { (*dependency_output.instances[*i].get()).is_some() });
+                    let index = selected.map(|selected| selected + 1).unwrap_or(*index);
+                    result
+                        .data
+                        .insert(*type_id, DependencyType::Try(index, selected));
+                    continue;
+                }
             }
         }
 
@@ -386,22 +538,22 @@ impl Manager {
 
     /// Apply requested by execution change of state
     pub fn apply_states_changes(&mut self) {
-        let mut changes = self.states_changes.lock().expect("Mutex to be locked");
-        while let Some(operation) = changes.pop_front() {
+        let changes = std::mem::take(&mut *self.states_changes.lock().expect("Mutex to be locked"));
+        for operation in changes.into_iter() {
             match operation {
                 StateChangeType::Push(state) => {
-                    self.states_stack.push(state);
+                    self.push_state_slot(state);
                 }
                 StateChangeType::Pop => {
                     if self.states_stack.len() > 1 {
-                        self.states_stack.pop();
+                        self.pop_state_slot();
                     }
                 }
                 StateChangeType::PopUntil(state_id) => {
                     while self.states_stack.len() > 1
                         && self.states_stack.last().unwrap().id != state_id
                     {
-                        self.states_stack.pop();
+                        self.pop_state_slot();
                     }
                 }
             }
@@ -410,9 +562,55 @@ impl Manager {
 
     /// Pushes single boxed state into stack
     pub fn push_state_slot(&mut self, state: StateSlot) {
+        self.transition_events.push((state.on_enter)());
         self.states_stack.push(state);
     }
 
+    /// Pops the last state out of the stack
+    fn pop_state_slot(&mut self) {
+        if let Some(state) = self.states_stack.pop() {
+            self.transition_events.push((state.on_exit)());
+        }
+    }
+
+    /// Registers type of a transition event, selected by a task
+    pub fn register_transition(&mut self, type_id: TypeId, name: String) {
+        if self.transitions.insert(type_id) {
+            self.register(type_id, name, 0, false);
+            self.set_scheduled_provisions(type_id, 0);
+        }
+    }
+
+    /// Sets number of provisions of transition events for the next loop
+    ///
+    /// Returns true if number of provisions has changed
+    pub fn schedule_transitions(&mut self) -> bool {
+        let mut changed = false;
+        for type_id in self.transitions.iter() {
+            let provisions = self
+                .transition_events
+                .iter()
+                .filter(|(event_type_id, _)| event_type_id == type_id)
+                .count();
+            if let Some(slot) = self.outputs.get_mut(type_id) {
+                if slot.scheduled != Some(provisions) {
+                    slot.scheduled = Some(provisions);
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Provides transition events, selected by tasks. The rest are dropped
+    pub fn provide_transitions(&mut self) {
+        for (type_id, event) in std::mem::take(&mut self.transition_events).into_iter() {
+            if self.transitions.contains(&type_id) {
+                self.provide(type_id, event);
+            }
+        }
+    }
+
     /// Calculates providers graph
     pub unsafe fn calculate_providers<T: Context>(
         &mut self,
@@ -453,15 +651,23 @@ pub trait Context: std::any::Any + 'static {}
 impl<T: 'static> Context for T {}
 
 impl StateSlot {
-    fn from<T: std::any::Any + Send + 'static>(data: T) -> Self {
+    /// Constructs state slot from data
+    pub fn from<T: std::any::Any + Send + 'static>(data: T) -> Self {
         Self {
             id: std::any::TypeId::of::<T>(),
             name: String::from(std::any::type_name::<T>()),
             data: UnsafeCell::new(Box::new(data)),
+            on_enter: transition::<OnEnter<T>>,
+            on_exit: transition::<OnExit<T>>,
         }
     }
 }
 
+/// Constructs transition event of type `T`
+fn transition<T: Default + Send + 'static>() -> (TypeId, Box<dyn std::any::Any + Send + 'static>) {
+    (TypeId::of::<T>(), Box::new(T::default()))
+}
+
 /// Enumeration of task dependencies types
 ///
 /// Encapsulated number represents number of provisions
@@ -471,6 +677,11 @@ pub enum DependencyType {
     Any(usize),
     /// Sattisfied when all of the data provisions available
     All(usize),
+    /// Sattisfied when all of the data provisions available, selects a new provision of data if
+    /// there is one
+    ///
+    /// Encapsulated numbers are number of provisions and index of the selected one
+    Try(usize, Option<usize>),
 }
 
 impl DependencyType {
@@ -479,6 +690,10 @@ impl DependencyType {
         match self {
             DependencyType::Any(index) => *index = 0,
             DependencyType::All(count) => *count = 0,
+            DependencyType::Try(index, selected) => {
+                *index = 0;
+                *selected = None;
+            }
         }
     }
 }
@@ -511,6 +726,7 @@ impl Clone for Dependencies {
                         match entry {
                             DependencyType::Any(_) => DependencyType::Any(0),
                             DependencyType::All(_) => DependencyType::All(0),
+                            DependencyType::Try(_, _) => DependencyType::Try(0, None),
                         },
                     )
                 })
@@ -549,6 +765,11 @@ pub trait Selector: Sized + Send + Sync {
     /// Very unsafe, only selectors like Take<T> can call this
     unsafe fn drop_data(&mut self) {}
 
+    /// Returns true if selector targets a state transition event
+    fn is_transition() -> bool {
+        false
+    }
+
     // / Returns Dependency
     //fn dependency_type() -> Option<(std::any::TypeId, DependencyType)> {
     //    None
@@ -565,6 +786,10 @@ pub trait ContextSelector: Sized {
     fn dependencies() -> Dependencies;
     /// Returns references list of states
     fn states() -> Vec<std::any::TypeId>;
+    /// Returns names of selected types
+    fn type_names() -> HashMap<TypeId, &'static str>;
+    /// Returns types of selected state transition events
+    fn transitions() -> Vec<TypeId>;
 }
 
 macro_rules! impl_context_selector {
@@ -617,6 +842,30 @@ macro_rules! impl_context_selector {
                 // states
             }
 
+            fn type_names() -> HashMap<TypeId, &'static str> {
+                [
+                    (
+                        std::any::TypeId::of::<scheduler::Loop>(),
+                        std::any::type_name::<scheduler::Loop>(),
+                    ),
+                    $((
+                        std::any::TypeId::of::<$i::DataSlot>(),
+                        std::any::type_name::<$i::DataSlot>(),
+                    ),)*
+                ]
+                    .into_iter()
+                    .collect::<HashMap<_, _>>()
+            }
+
+            fn transitions() -> Vec<TypeId> {
+                [ $(($i::target().0, $i::is_transition()),)* ]
+                    .into_iter()
+                    .filter_map(|(type_id, is_transition)| {
+                        if is_transition { Some(type_id) } else { None }
+                    })
+                    .collect::<Vec<_>>()
+            }
+
             fn dependencies() -> Dependencies {
                 let data = [
                     (
@@ -695,12 +944,49 @@ pub struct Take<T: Selector> {
     selection: T,
 }
 
+/// context::Selector that does not create a dependency on selected data
+///
+/// Task waits for the scheduled providers of the data, but runs regardless of availability of the
+/// data and receives `None`, if nothing was provided
+pub struct Try<T: Selector> {
+    selection: Option<T>,
+}
+
 /// Satate context selector
 pub struct State<T: Selector> {
     selection: T,
     changes: Arc<Mutex<VecDeque<StateChangeType>>>,
 }
 
+/// Event of entering the state `S`, selected once in the loop following the transition
+pub struct OnEnter<S> {
+    _phantom: PhantomData<fn() -> S>,
+}
+
+/// Event of exiting the state `S`, selected once in the loop following the transition
+///
+/// Tasks of the exited state are not executed anymore, so the event should be selected by tasks
+/// of the default or of the underlying state
+pub struct OnExit<S> {
+    _phantom: PhantomData<fn() -> S>,
+}
+
+impl<S> Default for OnEnter<S> {
+    fn default() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> Default for OnExit<S> {
+    fn default() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
 impl<T> Selector for Mut<T>
 where
     T: Context,
@@ -758,7 +1044,8 @@ where
             .get(&std::any::TypeId::of::<T>())
             .expect("Dependency to be consistant")
         {
-            DependencyType::Any(index) => *index,
+            // dependencies are matched, so the selected provision is the previous one
+            DependencyType::Any(index) => index.checked_sub(1)?,
             _ => panic!("Dependency and accessor missmatch"),
         };
         manager.select_any(index)
@@ -822,7 +1109,8 @@ where
             .get(&std::any::TypeId::of::<T>())
             .expect("Dependency to be consistant")
         {
-            DependencyType::Any(index) => *index,
+            // dependencies are matched, so the selected provision is the previous one
+            DependencyType::Any(index) => index.checked_sub(1)?,
             _ => panic!("Dependency and accessor missmatch"),
         };
 
@@ -856,6 +1144,164 @@ where
     }
 }
 
+impl<T> Selector for Try<Ref<T>>
+where
+    T: Context,
+{
+    type DataSlot = T;
+
+    fn target() -> (std::any::TypeId, SelectorTarget) {
+        (std::any::TypeId::of::<T>(), SelectorTarget::Global)
+    }
+
+    unsafe fn select(manager: &Manager, _: &Dependencies) -> Option<Self> {
+        Some(Try {
+            selection: manager.select_ref::<T>(),
+        })
+    }
+
+    fn lock_type() -> Option<Lock> {
+        Some(Lock::ReadOnly(std::any::TypeId::of::<T>()))
+    }
+}
+
+impl<T> Selector for Try<Mut<T>>
+where
+    T: Context,
+{
+    type DataSlot = T;
+
+    fn target() -> (std::any::TypeId, SelectorTarget) {
+        (std::any::TypeId::of::<T>(), SelectorTarget::Global)
+    }
+
+    unsafe fn select(manager: &Manager, _: &Dependencies) -> Option<Self> {
+        Some(Try {
+            selection: manager.select_mut::<T>(),
+        })
+    }
+
+    fn lock_type() -> Option<Lock> {
+        Some(Lock::ReadWrite(std::any::TypeId::of::<T>()))
+    }
+}
+
+impl<T> Selector for Try<Any<T>>
+where
+    T: Context,
+{
+    type DataSlot = T;
+
+    fn target() -> (std::any::TypeId, SelectorTarget) {
+        (
+            std::any::TypeId::of::<T>(),
+            SelectorTarget::Output(DependencyType::Try(0, None)),
+        )
+    }
+
+    unsafe fn select(manager: &Manager, dependencies: &Dependencies) -> Option<Self> {
+        let selected = match dependencies
+            .data
+            .get(&std::any::TypeId::of::<T>())
+            .expect("Dependency to be consistant")
+        {
+            DependencyType::Try(_, selected) => *selected,
+            _ => panic!("Dependency and accessor missmatch"),
+        };
+        Some(Try {
+            selection: selected.and_then(|index| manager.select_any::<T>(index)),
+        })
+    }
+
+    fn lock_type() -> Option<Lock> {
+        Some(Lock::ReadOnly(std::any::TypeId::of::<T>()))
+    }
+}
+
+impl<T> Selector for Try<Take<Any<T>>>
+where
+    T: Context,
+{
+    type DataSlot = T;
+
+    fn target() -> (std::any::TypeId, SelectorTarget) {
+        (
+            std::any::TypeId::of::<T>(),
+            SelectorTarget::Output(DependencyType::Try(0, None)),
+        )
+    }
+
+    unsafe fn select(manager: &Manager, dependencies: &Dependencies) -> Option<Self> {
+        let selected = match dependencies
+            .data
+            .get(&std::any::TypeId::of::<T>())
+            .expect("Dependency to be consistant")
+        {
+            DependencyType::Try(_, selected) => *selected,
+            _ => panic!("Dependency and accessor missmatch"),
+        };
+        Some(Try {
+            selection: selected.and_then(|index| manager.take_any::<T>(index)),
+        })
+    }
+
+    fn lock_type() -> Option<Lock> {
+        Some(Lock::ReadWrite(std::any::TypeId::of::<T>()))
+    }
+}
+
+impl<S> Selector for OnEnter<S>
+where
+    S: Context,
+{
+    type DataSlot = OnEnter<S>;
+
+    fn target() -> (std::any::TypeId, SelectorTarget) {
+        (
+            std::any::TypeId::of::<Self>(),
+            SelectorTarget::Output(DependencyType::Any(0)),
+        )
+    }
+
+    unsafe fn select(manager: &Manager, _dependencies: &Dependencies) -> Option<Self> {
+        if manager.is_provided(&std::any::TypeId::of::<Self>()) {
+            Some(Self::default())
+        } else {
+            None
+        }
+    }
+
+    fn is_transition() -> bool {
+        true
+    }
+}
+
+impl<S> Selector for OnExit<S>
+where
+    S: Context,
+{
+    type DataSlot = OnExit<S>;
+
+    fn target() -> (std::any::TypeId, SelectorTarget) {
+        (
+            std::any::TypeId::of::<Self>(),
+            SelectorTarget::Output(DependencyType::Any(0)),
+        )
+    }
+
+    unsafe fn select(manager: &Manager, _dependencies: &Dependencies) -> Option<Self> {
+        if manager.is_provided(&std::any::TypeId::of::<Self>()) {
+            Some(Self::default())
+        } else {
+            None
+        }
+    }
+
+    fn is_transition() -> bool {
+        true
+    }
+}
+
 impl<T> Selector for State<Ref<T>>
 where
     T: Context,
@@ -1093,6 +1539,26 @@ impl<T: Selector> Drop for Take<T> {
     }
 }
 
+impl<T: Selector> Try<T> {
+    /// Returns selected data, if there is any
+    pub fn into_inner(self) -> Option<T> {
+        self.selection
+    }
+}
+
+impl<T: Selector> Deref for Try<T> {
+    type Target = Option<T>;
+    fn deref(&self) -> &Self::Target {
+        &self.selection
+    }
+}
+
+impl<T: Selector> DerefMut for Try<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.selection
+    }
+}
+
 impl<T: Selector> State<T> {
     /// Pushes new state to the stack.
     ///
@@ -1131,3 +1597,250 @@ unsafe impl<T: Context> Send for Any<T> {}
 unsafe impl<T: Context> Sync for Any<T> {}
 unsafe impl<T: Context> Send for All<T> {}
 unsafe impl<T: Context> Sync for All<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{All, Any, Mut, OnEnter, OnExit, Ref, State, Take, Try};
+    use crate::graphics::Frame;
+    use crate::headless::Headless;
+    use crate::tasks::{Condition, Order, OutputChannel, Task};
+
+    struct Menu;
+
+    struct Game;
+
+    #[derive(Default, Clone, Debug, PartialEq)]
+    struct Log {
+        entered_menu: u32,
+        exited_menu: u32,
+        entered_game: u32,
+        exited_game: u32,
+        game_frames: u32,
+    }
+
+    struct Handled;
+
+    struct Director;
+
+    impl Task for Director {
+        type Context = (Any<Frame>, State<Ref<()>>);
+        type Output = Handled;
+
+        fn run(&mut self, (frame, state): Self::Context) -> Self::Output {
+            match frame.number {
+                2 => state.push(Menu),
+                4 => state.push(Game),
+                6 => state.pop(),
+                8 => state.pop_until::<()>(),
+                _ => {}
+            };
+            Handled
+        }
+    }
+
+    struct EnterMenu;
+
+    impl Task for EnterMenu {
+        type Context = (OnEnter<Menu>, Mut<Log>);
+        type Output = Handled;
+
+        fn run(&mut self, (_, mut log): Self::Context) -> Self::Output {
+            log.entered_menu += 1;
+            Handled
+        }
+    }
+
+    struct ExitMenu;
+
+    impl Task for ExitMenu {
+        type Context = (OnExit<Menu>, Mut<Log>);
+        type Output = Handled;
+
+        fn run(&mut self, (_, mut log): Self::Context) -> Self::Output {
+            log.exited_menu += 1;
+            Handled
+        }
+    }
+
+    struct EnterGame;
+
+    impl Task for EnterGame {
+        type Context = (OnEnter<Game>, Mut<Log>);
+        type Output = Handled;
+
+        fn run(&mut self, (_, mut log): Self::Context) -> Self::Output {
+            log.entered_game += 1;
+            Handled
+        }
+    }
+
+    struct ExitGame;
+
+    impl Task for ExitGame {
+        type Context = (OnExit<Game>, Mut<Log>);
+        type Output = Handled;
+
+        fn run(&mut self, (_, mut log): Self::Context) -> Self::Output {
+            log.exited_game += 1;
+            Handled
+        }
+    }
+
+    struct PlayGame;
+
+    impl Task for PlayGame {
+        type Context = (Any<Frame>, State<Ref<Game>>, Mut<Log>);
+        type Output = Handled;
+
+        fn run(&mut self, (_, _, mut log): Self::Context) -> Self::Output {
+            log.game_frames += 1;
+            Handled
+        }
+    }
+
+    struct Snapshot(u64, Log);
+
+    struct Report;
+
+    impl Task for Report {
+        type Context = (Any<Frame>, All<Handled>, Ref<Log>);
+        type Output = Snapshot;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (frame, _, log): Self::Context) -> Self::Output {
+            Snapshot(frame.number, log.clone())
+        }
+    }
+
+    #[test]
+    fn transition_events_fire_once() {
+        let headless = Headless::<Snapshot>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Log::default());
+            scheduler.add_task(Report);
+            scheduler.add_task(Director);
+            scheduler.add_task(EnterMenu);
+            scheduler.add_task(ExitMenu);
+            scheduler.add_task(EnterGame);
+            scheduler.add_task(ExitGame);
+            scheduler.add_task(PlayGame);
+        }
+
+        let expected = [
+            (3, [1, 0, 0, 0, 0]),
+            (5, [1, 0, 1, 0, 1]),
+            (6, [1, 0, 1, 0, 2]),
+            (7, [1, 0, 1, 1, 2]),
+            (9, [1, 1, 1, 1, 2]),
+            (12, [1, 1, 1, 1, 2]),
+        ];
+        let mut expected = expected.iter().peekable();
+        while let Some((number, counters)) = expected.peek() {
+            let Snapshot(frame, log) = headless.step();
+            if frame == *number {
+                assert_eq!(
+                    [
+                        log.entered_menu,
+                        log.exited_menu,
+                        log.entered_game,
+                        log.exited_game,
+                        log.game_frames
+                    ],
+                    *counters,
+                    "frame {}",
+                    frame
+                );
+                expected.next();
+            }
+        }
+    }
+
+    struct Hint(u64);
+
+    struct Missing;
+
+    #[derive(Default, Clone, Debug, PartialEq)]
+    struct Seen {
+        peeked: Vec<Option<u64>>,
+        taken: Vec<u64>,
+        missing: bool,
+    }
+
+    struct Sometimes;
+
+    impl Task for Sometimes {
+        type Context = (Any<Frame>,);
+        type Output = Hint;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::every(2)]
+        }
+
+        fn run(&mut self, (frame,): Self::Context) -> Self::Output {
+            Hint(frame.number)
+        }
+    }
+
+    struct Peek;
+
+    impl Task for Peek {
+        type Context = (Any<Frame>, Try<Any<Hint>>, Try<Ref<Missing>>, Mut<Seen>);
+        type Output = ();
+
+        fn run(&mut self, (_, hint, missing, mut seen): Self::Context) -> Self::Output {
+            seen.peeked.push(hint.as_ref().map(|hint| hint.0));
+            seen.missing = missing.is_none();
+        }
+    }
+
+    struct Collect;
+
+    impl Task for Collect {
+        type Context = (Any<Frame>, Try<Take<Any<Hint>>>, Try<Mut<Seen>>);
+        type Output = Seen;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn after(&self) -> Vec<Order> {
+            vec![Order::task::<Peek>()]
+        }
+
+        fn run(&mut self, (_, hint, seen): Self::Context) -> Self::Output {
+            let mut seen = seen.into_inner().expect("Seen to be in the context");
+            if let Some(hint) = hint.into_inner() {
+                seen.taken.push(hint.0);
+            }
+            seen.clone()
+        }
+    }
+
+    #[test]
+    fn try_selector_does_not_wait_for_data() {
+        let headless = Headless::<Seen>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Seen::default());
+            scheduler.add_task(Collect);
+            scheduler.add_task(Peek);
+            scheduler.add_task(Sometimes);
+        }
+        let mut seen = Seen::default();
+        for _ in 0..5 {
+            seen = headless.step();
+        }
+        assert_eq!(
+            seen,
+            Seen {
+                peeked: vec![Some(1), None, Some(3), None, Some(5)],
+                taken: vec![1, 3, 5],
+                missing: true,
+            }
+        );
+    }
+}
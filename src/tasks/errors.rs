@@ -0,0 +1,137 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Maximal number of failures kept by [`Errors`], older ones are dropped
+const CAPACITY: usize = 128;
+
+/// Policy applied to a task, that has panicked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Task is paused, until resumed by the application
+    #[default]
+    Disable,
+    /// Task is executed again in the next loop
+    Retry,
+    /// Application is aborted
+    Abort,
+}
+
+/// Record of a task failure
+#[derive(Debug, Clone)]
+pub struct Failure {
+    /// Name of the task
+    pub task: String,
+    /// Panic message
+    pub message: String,
+    /// Number of the tasks cycle (frame), when the failure happened
+    pub cycle: u64,
+    /// Policy applied to the task
+    pub policy: FailurePolicy,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Task {} has panicked in cycle #{}: {}",
+            self.task, self.cycle, self.message
+        )
+    }
+}
+
+/// Failures of tasks
+///
+/// Stored in the global context by the scheduler, so tasks can access it using `Ref<Errors>`.
+/// Cloned instances share the same records.
+#[derive(Debug, Default, Clone)]
+pub struct Errors {
+    failures: Arc<Mutex<VecDeque<Failure>>>,
+}
+
+impl Errors {
+    /// Stores the failure
+    pub fn record(&self, failure: Failure) {
+        let mut failures = self.failures.lock().expect("Mutex to be locked");
+        if failures.len() == CAPACITY {
+            failures.pop_front();
+        }
+        failures.push_back(failure);
+    }
+
+    /// Returns copy of recorded failures
+    pub fn failures(&self) -> Vec<Failure> {
+        self.failures
+            .lock()
+            .expect("Mutex to be locked")
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns true if there are no recorded failures
+    pub fn is_empty(&self) -> bool {
+        self.failures.lock().expect("Mutex to be locked").is_empty()
+    }
+
+    /// Removes all recorded failures
+    pub fn clear(&self) {
+        self.failures.lock().expect("Mutex to be locked").clear();
+    }
+}
+
+/// Report of the loop, abandoned due to failures of tasks
+///
+/// Returned instead of the awaited output, that was not provided in the loop
+#[derive(Debug, Clone)]
+pub struct Abandoned {
+    /// Number of the tasks cycle (frame)
+    pub cycle: u64,
+    /// Panics of tasks recorded in the cycle, errors of fallible tasks are provided as
+    /// [`TaskError`]
+    pub failures: Vec<Failure>,
+}
+
+impl std::fmt::Display for Abandoned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Loop #{} is abandoned due to failures of tasks",
+            self.cycle
+        )?;
+        for failure in self.failures.iter() {
+            write!(f, "; {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Abandoned {}
+
+/// Error of a fallible task, provided to the central error channel
+///
+/// Errors are kept until taken, so tasks consume them using `Take<All<TaskError>>`
+#[derive(Debug)]
+pub struct TaskError {
+    /// Name of the task
+    pub task: &'static str,
+    /// Error returned by the task
+    pub error: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl std::fmt::Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Task {} has failed: {}", self.task, self.error)
+    }
+}
+
+/// Returns message of the panic payload
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        String::from(*message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("UNKNOWN")
+    }
+}
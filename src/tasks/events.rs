@@ -0,0 +1,278 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+/// Default number of loops, during which events are retained
+const DEFAULT_RETENTION: u64 = 2;
+
+struct Entry<T> {
+    /// Sequential number of the event
+    id: u64,
+    /// Loop, when the event was sent
+    stamp: u64,
+    /// Event data
+    data: T,
+}
+
+/// Typed event channel
+///
+/// Being added to the scheduler, it is stored in the global context and updated at the beginning
+/// of every loop. Tasks send events using `Mut<Events<T>>` and read them using `Ref<Events<T>>`
+/// together with own [`EventReader`], so every reader sees every event once.
+///
+/// Event is retained for the specified number of loops. With retention of one loop, readers must
+/// be executed after writers in the same loop, otherwise they could miss events. Retention of
+/// two loops works as a double-buffered queue: readers executed once per loop see every event
+/// independently of the execution order.
+pub struct Events<T> {
+    entries: VecDeque<Entry<T>>,
+    next_id: u64,
+    loops: u64,
+    retention: u64,
+}
+
+impl<T> Events<T> {
+    /// Constructs new event channel, retaining events for `retention` loops (at least one)
+    pub fn new(retention: u64) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            next_id: 0,
+            loops: 0,
+            retention: retention.max(1),
+        }
+    }
+
+    /// Sends an event
+    pub fn send(&mut self, event: T) {
+        self.entries.push_back(Entry {
+            id: self.next_id,
+            stamp: self.loops,
+            data: event,
+        });
+        self.next_id += 1;
+    }
+
+    /// Sends several events
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Returns iterator across all retained events
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().map(|entry| &entry.data)
+    }
+
+    /// Returns number of retained events
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no retained events
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops all retained events
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Starts a new loop, dropping outdated events
+    ///
+    /// Called by the scheduler, if the channel was added using
+    /// [`crate::tasks::Scheduler::add_events`]
+    pub fn update(&mut self) {
+        self.loops += 1;
+        while let Some(entry) = self.entries.front() {
+            if entry.stamp + self.retention > self.loops {
+                break;
+            }
+            self.entries.pop_front();
+        }
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETENTION)
+    }
+}
+
+/// Updates boxified event channel of type `T`
+pub fn update<T: 'static>(events: &mut (dyn Any + Send)) {
+    if let Some(events) = events.downcast_mut::<Events<T>>() {
+        events.update();
+    }
+}
+
+/// Cursor of an events reader
+///
+/// Every task, that reads events, owns its own reader, so readers don't affect each other
+pub struct EventReader<T> {
+    cursor: u64,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> EventReader<T> {
+    /// Constructs new reader, that reads all retained events
+    pub fn new() -> Self {
+        Self {
+            cursor: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns iterator across events, that were not read by the reader yet
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let first = events
+            .entries
+            .front()
+            .map(|entry| entry.id)
+            .unwrap_or(events.next_id);
+        let skip = self.cursor.saturating_sub(first) as usize;
+        self.cursor = events.next_id;
+        events.entries.iter().skip(skip).map(|entry| &entry.data)
+    }
+
+    /// Returns number of events, that were not read by the reader yet
+    pub fn unread(&self, events: &Events<T>) -> usize {
+        events
+            .entries
+            .iter()
+            .rev()
+            .take_while(|entry| entry.id >= self.cursor)
+            .count()
+    }
+
+    /// Marks all retained events as read
+    pub fn skip(&mut self, events: &Events<T>) {
+        self.cursor = events.next_id;
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventReader, Events};
+    use crate::graphics::Frame;
+    use crate::headless::Headless;
+    use crate::tasks::{Any, Mut, OutputChannel, Ref, Task};
+
+    struct Damage(u32);
+
+    struct Sent;
+
+    struct Hit;
+
+    impl Task for Hit {
+        type Context = (Any<Frame>, Mut<Events<Damage>>);
+        type Output = Sent;
+
+        fn run(&mut self, (frame, mut events): Self::Context) -> Self::Output {
+            if frame.number <= 3 {
+                events.send(Damage(frame.number as u32));
+            }
+            if frame.number == 2 {
+                events.send(Damage(100));
+            }
+            Sent
+        }
+    }
+
+    struct Health(u32);
+
+    #[derive(Default)]
+    struct ApplyDamage {
+        reader: EventReader<Damage>,
+        total: u32,
+    }
+
+    impl Task for ApplyDamage {
+        type Context = (Any<Frame>, Ref<Events<Damage>>);
+        type Output = Health;
+
+        fn run(&mut self, (_frame, events): Self::Context) -> Self::Output {
+            self.total += self
+                .reader
+                .read(&events)
+                .map(|damage| damage.0)
+                .sum::<u32>();
+            Health(self.total)
+        }
+    }
+
+    #[derive(Default)]
+    struct LogDamage {
+        reader: EventReader<Damage>,
+        total: u32,
+    }
+
+    impl Task for LogDamage {
+        type Context = (Any<Sent>, Ref<Events<Damage>>, Any<Health>);
+        type Output = (u32, u32);
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_sent, events, health): Self::Context) -> Self::Output {
+            self.total += self
+                .reader
+                .read(&events)
+                .map(|damage| damage.0)
+                .sum::<u32>();
+            (self.total, health.0)
+        }
+    }
+
+    #[test]
+    fn events_are_read_independently_by_readers() {
+        let headless = Headless::<(u32, u32)>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_events(Events::<Damage>::new(2));
+            scheduler.add_task(LogDamage::default());
+            scheduler.add_task(ApplyDamage::default());
+            scheduler.add_task(Hit);
+        }
+        headless.run_frames(4);
+        // every event is read once by every reader, regardless of the execution order
+        assert_eq!(headless.step(), (106, 106));
+    }
+
+    #[test]
+    fn events_are_dropped_after_retention() {
+        let mut events = Events::new(2);
+        let mut reader = EventReader::default();
+        events.send(1);
+        events.send(2);
+        assert_eq!(reader.unread(&events), 2);
+        assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(reader.unread(&events), 0);
+
+        events.update();
+        events.send(3);
+        assert_eq!(events.len(), 3);
+        assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [3]);
+
+        events.update();
+        assert_eq!(events.iter().copied().collect::<Vec<_>>(), [3]);
+
+        let mut late_reader = EventReader::default();
+        events.update();
+        assert!(events.is_empty());
+        assert_eq!(late_reader.read(&events).count(), 0);
+
+        let mut single = Events::new(1);
+        single.send(1);
+        single.update();
+        assert!(single.is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use std::any::{Any, TypeId};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::executor::LocalPool;
+use futures::task::LocalSpawnExt;
+use futures::{FutureExt, StreamExt};
+
+use super::{errors, scheduler, task};
+use crate::log;
+use crate::utils::Id;
+
+/// Boxified future of an async task output
+pub type Pending = Pin<Box<dyn Future<Output = Box<dyn Any + 'static + Send>> + Send>>;
+
+/// Future to be polled by the executor
+pub struct Job {
+    /// Id of the async task
+    pub task: Id<task::Slot>,
+    /// Type id of the future output
+    pub type_id: TypeId,
+    /// Boxified future
+    pub future: Pending,
+    /// Failure to be reported, if the future panics
+    pub failure: errors::Failure,
+}
+
+/// Launches executor thread, that polls futures of async tasks and provides their outputs to
+/// the scheduler
+///
+/// Thread stops, when the scheduler drops its sender of jobs. Unresolved futures are dropped.
+/// Panicked futures are reported to the scheduler as failures of their tasks.
+pub fn spawn(
+    rx: UnboundedReceiver<Job>,
+    tx: Arc<Mutex<mpsc::Sender<scheduler::Message>>>,
+) -> thread::JoinHandle<()> {
+    let name = String::from("dotrix::executor");
+    thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            log::info!("started: {}", name);
+            let mut pool = LocalPool::new();
+            let spawner = pool.spawner();
+            pool.run_until(rx.for_each(|job| {
+                let tx = Arc::clone(&tx);
+                let result = spawner.spawn_local(async move {
+                    let message = match AssertUnwindSafe(job.future).catch_unwind().await {
+                        Ok(data) => scheduler::Message::Provide(job.type_id, data),
+                        Err(payload) => {
+                            let failure = errors::Failure {
+                                message: errors::panic_message(payload.as_ref()),
+                                ..job.failure
+                            };
+                            scheduler::Message::AsyncFailure(job.task, job.type_id, failure)
+                        }
+                    };
+                    tx.lock().expect("Mutex to be locked").send(message).ok();
+                });
+                if result.is_err() {
+                    log::error!("{}: could not spawn a future", name);
+                }
+                futures::future::ready(())
+            }));
+            log::info!("{} goes off", name);
+        })
+        .expect("Thread to be spawned")
+}
@@ -0,0 +1,674 @@
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::utils::{Id, Lock};
+
+use super::{context, scheduler, task};
+
+/// Snapshot of the tasks graph
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    /// Tasks of the application
+    pub tasks: Vec<Node>,
+    /// Outputs produced or consumed by the tasks
+    pub outputs: Vec<OutputNode>,
+}
+
+/// Task node of the graph
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// Task id
+    pub id: Id<task::Slot>,
+    /// Task name
+    pub name: String,
+    /// Name of the output type
+    pub output: String,
+    /// Type id of the output
+    pub output_type_id: TypeId,
+    /// States, the task is running in. Empty for the default state
+    pub states: Vec<String>,
+    /// Context types locked by the task during execution
+    pub locks: Vec<(String, Access)>,
+    /// Outputs, the task depends on
+    pub dependencies: Vec<Dependency>,
+    /// True if task is selected for execution in the current state
+    pub queued: bool,
+    /// True if task is being executed by a worker
+    pub running: bool,
+    /// True if task is paused
+    pub paused: bool,
+}
+
+/// Output node of the graph
+#[derive(Debug, Clone)]
+pub struct OutputNode {
+    /// Type id of the output
+    pub type_id: TypeId,
+    /// Name of the output type
+    pub name: String,
+    /// Number of expected provisions per loop
+    pub providers: usize,
+    /// True if output is provided outside of tasks: registered or by the scheduler
+    pub external: bool,
+}
+
+/// Task dependency on an output
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    /// Type id of the output
+    pub type_id: TypeId,
+    /// Name of the output type
+    pub name: String,
+    /// Selection mode
+    pub selection: Selection,
+}
+
+/// Selection mode of a dependency
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Selection {
+    /// Task runs for any provision
+    Any,
+    /// Task runs when all provisions are available
+    All,
+    /// Task runs regardless of provisions
+    Try,
+}
+
+/// Error of the tasks graph, that prevents tasks from being executed
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Error {
+    /// Task depends on an output, that nobody provides
+    MissingProvider {
+        /// Name of the task
+        task: String,
+        /// Name of the missing output
+        output: String,
+    },
+    /// Tasks depend on outputs of each other
+    Cycle {
+        /// Names of the tasks in the cycle
+        tasks: Vec<String>,
+    },
+    /// Task locks the same context for writing more than once or for reading and writing
+    LockConflict {
+        /// Name of the task
+        task: String,
+        /// Name of the context type
+        context: String,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingProvider { task, output } => {
+                write!(
+                    f,
+                    "Task {} depends on {}, that is never provided",
+                    task, output
+                )
+            }
+            Error::Cycle { tasks } => {
+                write!(f, "Tasks depend on each other: {}", tasks.join(" -> "))
+            }
+            Error::LockConflict { task, context } => {
+                write!(f, "Task {} has conflicting locks of {}", task, context)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Access mode of a lock
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Access {
+    /// Shared read-only access
+    ReadOnly,
+    /// Exclusive read-write access
+    ReadWrite,
+}
+
+impl From<&scheduler::Task> for Node {
+    fn from(task: &scheduler::Task) -> Self {
+        let type_names = task.type_names();
+        let type_name = |type_id: &TypeId| {
+            type_names
+                .get(type_id)
+                .map(|name| String::from(*name))
+                .unwrap_or_else(|| format!("{:?}", type_id))
+        };
+
+        let mut dependencies = task
+            .dependencies()
+            .data
+            .iter()
+            .map(|(type_id, dependency_type)| Dependency {
+                type_id: *type_id,
+                name: type_name(type_id),
+                selection: match dependency_type {
+                    context::DependencyType::Any(_) => Selection::Any,
+                    context::DependencyType::All(_) => Selection::All,
+                    context::DependencyType::Try(_, _) => Selection::Try,
+                },
+            })
+            .collect::<Vec<_>>();
+        dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            id: task.id(),
+            name: String::from(task.name()),
+            output: String::from(task.output_as_str()),
+            output_type_id: task.output_type_id(),
+            states: task.states().iter().map(type_name).collect(),
+            locks: task
+                .lock()
+                .iter()
+                .map(|lock| match lock {
+                    Lock::ReadOnly(type_id) => (type_name(type_id), Access::ReadOnly),
+                    Lock::ReadWrite(type_id) => (type_name(type_id), Access::ReadWrite),
+                })
+                .collect(),
+            dependencies,
+            queued: false,
+            running: false,
+            paused: false,
+        }
+    }
+}
+
+impl Graph {
+    /// Validates tasks, selected for execution in the current state
+    pub fn validate(&self) -> Result<(), Vec<Error>> {
+        let queued = self
+            .tasks
+            .iter()
+            .filter(|node| node.queued)
+            .collect::<Vec<_>>();
+        let external = self
+            .outputs
+            .iter()
+            .filter(|output| output.external)
+            .map(|output| output.type_id)
+            .collect::<HashSet<_>>();
+        let mut errors = vec![];
+
+        for node in queued.iter() {
+            for dependency in node.dependencies.iter() {
+                // paused and throttled tasks may provide the output later
+                if dependency.selection == Selection::Any
+                    && !external.contains(&dependency.type_id)
+                    && !self
+                        .tasks
+                        .iter()
+                        .any(|provider| provider.output_type_id == dependency.type_id)
+                {
+                    errors.push(Error::MissingProvider {
+                        task: node.name.clone(),
+                        output: dependency.name.clone(),
+                    });
+                }
+            }
+
+            let mut locks = HashMap::new();
+            for (name, access) in node.locks.iter() {
+                let read_write = *access == Access::ReadWrite;
+                if let Some(locked_for_write) = locks.insert(name, read_write) {
+                    if locked_for_write || read_write {
+                        errors.push(Error::LockConflict {
+                            task: node.name.clone(),
+                            context: name.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        // indices of tasks, consuming output of a task
+        let consumers = queued
+            .iter()
+            .map(|provider| {
+                queued
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, consumer)| {
+                        // optional dependencies never block the consumer
+                        consumer.dependencies.iter().any(|dependency| {
+                            dependency.type_id == provider.output_type_id
+                                && dependency.selection != Selection::Try
+                        })
+                    })
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut visited = vec![false; queued.len()];
+        for root in 0..queued.len() {
+            let mut path = vec![];
+            find_cycles(root, &consumers, &mut visited, &mut path, &mut |cycle| {
+                errors.push(Error::Cycle {
+                    tasks: cycle
+                        .iter()
+                        .map(|&index| queued[index].name.clone())
+                        .collect(),
+                });
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Serializes the graph into Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tasks {\n    rankdir=LR;\n");
+
+        for output in self.outputs.iter() {
+            writeln!(
+                dot,
+                "    \"{}\" [shape=ellipse, label=\"{}\\nproviders: {}\"];",
+                escape(&output.name),
+                escape(&output.name),
+                output.providers
+            )
+            .ok();
+        }
+
+        for node in self.tasks.iter() {
+            let mut label = escape(&node.name);
+            for (name, access) in node.locks.iter() {
+                let access = match access {
+                    Access::ReadOnly => "ro",
+                    Access::ReadWrite => "rw",
+                };
+                write!(label, "\\n{}: {}", access, escape(name)).ok();
+            }
+            if !node.states.is_empty() {
+                write!(label, "\\nstates: {}", escape(&node.states.join(", "))).ok();
+            }
+            let style = if node.queued { "solid" } else { "dashed" };
+            writeln!(
+                dot,
+                "    \"{}\" [shape=box, style={}, label=\"{}\"];",
+                node.id.uuid(),
+                style,
+                label
+            )
+            .ok();
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\";",
+                node.id.uuid(),
+                escape(&node.output)
+            )
+            .ok();
+            for dependency in node.dependencies.iter() {
+                let selection = match dependency.selection {
+                    Selection::Any => "Any",
+                    Selection::All => "All",
+                    Selection::Try => "Try",
+                };
+                writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                    escape(&dependency.name),
+                    node.id.uuid(),
+                    selection
+                )
+                .ok();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Serializes the graph into JSON format
+    pub fn to_json(&self) -> String {
+        let strings = |list: &mut dyn Iterator<Item = &String>| {
+            list.map(|name| format!("\"{}\"", escape(name)))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let tasks = self
+            .tasks
+            .iter()
+            .map(|node| {
+                let locks = node
+                    .locks
+                    .iter()
+                    .map(|(name, access)| {
+                        format!(
+                            "{{\"type\":\"{}\",\"access\":\"{:?}\"}}",
+                            escape(name),
+                            access
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let dependencies = node
+                    .dependencies
+                    .iter()
+                    .map(|dependency| {
+                        format!(
+                            "{{\"type\":\"{}\",\"selection\":\"{:?}\"}}",
+                            escape(&dependency.name),
+                            dependency.selection
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!(
+                    concat!(
+                        "{{\"id\":\"{}\",\"name\":\"{}\",\"output\":\"{}\",\"states\":[{}],",
+                        "\"locks\":[{}],\"dependencies\":[{}],\"queued\":{},\"running\":{},",
+                        "\"paused\":{}}}"
+                    ),
+                    node.id.uuid(),
+                    escape(&node.name),
+                    escape(&node.output),
+                    strings(&mut node.states.iter()),
+                    locks,
+                    dependencies,
+                    node.queued,
+                    node.running,
+                    node.paused
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| {
+                format!(
+                    "{{\"type\":\"{}\",\"providers\":{}}}",
+                    escape(&output.name),
+                    output.providers
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!("{{\"tasks\":[{}],\"outputs\":[{}]}}", tasks, outputs)
+    }
+}
+
+/// Depth-first search of cycles, every cycle is reported once
+fn find_cycles(
+    index: usize,
+    consumers: &[Vec<usize>],
+    visited: &mut [bool],
+    path: &mut Vec<usize>,
+    report: &mut dyn FnMut(&[usize]),
+) {
+    if let Some(position) = path.iter().position(|&i| i == index) {
+        report(&path[position..]);
+        return;
+    }
+    if visited[index] {
+        return;
+    }
+    visited[index] = true;
+    path.push(index);
+    for &consumer in consumers[index].iter() {
+        find_cycles(consumer, consumers, visited, path, report);
+    }
+    path.pop();
+}
+
+/// Escapes string to be used inside of double quotes in DOT and JSON
+pub fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                write!(result, "\\u{:04x}", c as u32).ok();
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, Dependency, Error, Graph, Node, OutputNode, Selection};
+    use crate::graphics::Frame;
+    use crate::headless::Headless;
+    use crate::tasks::{All, Any, Condition, Mut, OutputChannel, Ref, Task};
+
+    #[derive(Default)]
+    struct Score(u32);
+
+    struct Done;
+
+    struct Missing;
+
+    struct UpdateScore;
+
+    impl Task for UpdateScore {
+        type Context = (Any<Frame>, Mut<Score>);
+        type Output = u32;
+
+        fn run(&mut self, (_frame, mut score): Self::Context) -> Self::Output {
+            score.0 += 1;
+            score.0
+        }
+    }
+
+    struct Stalled;
+
+    impl Task for Stalled {
+        type Context = (Any<Missing>,);
+        type Output = ();
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {}
+    }
+
+    struct Finish;
+
+    impl Task for Finish {
+        type Context = (All<u32>,);
+        type Output = Done;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {
+            Done
+        }
+    }
+
+    #[test]
+    fn can_export_graph() {
+        let headless = Headless::<Done>::new(1);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Score::default());
+            scheduler.add_task(UpdateScore);
+            scheduler.add_task(Finish);
+        }
+        headless.step();
+
+        let graph = headless.task_manager().graph();
+        assert_eq!(graph.tasks.len(), 3);
+        assert!(graph.validate().is_ok());
+
+        let update_score = graph
+            .tasks
+            .iter()
+            .find(|node| node.name.ends_with("UpdateScore"))
+            .expect("UpdateScore to be in the graph");
+        assert_eq!(update_score.output, "u32");
+        assert!(update_score.queued);
+        assert!(update_score
+            .locks
+            .iter()
+            .any(|(name, access)| name.ends_with("Score") && *access == Access::ReadWrite));
+        assert!(update_score
+            .dependencies
+            .iter()
+            .any(|dependency| dependency.name.ends_with("Frame")
+                && dependency.selection == Selection::Any));
+
+        let score_output = graph
+            .outputs
+            .iter()
+            .find(|output| output.name == "u32")
+            .expect("u32 output to be in the graph");
+        assert_eq!(score_output.providers, 1);
+        assert!(!graph.tasks.iter().any(|node| node.running));
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph tasks {"));
+        assert!(dot.contains("[label=\"All\"]"));
+
+        let json = graph.to_json();
+        assert!(json.starts_with("{\"tasks\":["));
+        assert!(json.contains("\"selection\":\"All\""));
+        assert!(json.contains("\"type\":\"u32\",\"providers\":1"));
+    }
+
+    #[test]
+    fn reports_missing_provider() {
+        let headless = Headless::<Done>::new(1);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Score::default());
+            scheduler.add_task(UpdateScore);
+            scheduler.add_task(Finish);
+            scheduler.add_task(Stalled);
+        }
+        // graph is validated against the queue of the started loop
+        headless.task_manager().run();
+        let mut result = Ok(());
+        for _ in 0..100 {
+            result = headless.task_manager().validate();
+            if result.is_err() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let errors = result.expect_err("Graph to be invalid");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0],
+            Error::MissingProvider { task, output }
+                if task.ends_with("Stalled") && output.ends_with("Missing")
+        ));
+    }
+
+    struct Sample;
+
+    impl Task for Sample {
+        type Context = (Any<Frame>,);
+        type Output = u8;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::every(2)]
+        }
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {
+            0
+        }
+    }
+
+    struct ReadSample;
+
+    impl Task for ReadSample {
+        type Context = (Any<u8>, Ref<Score>);
+        type Output = ();
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {}
+    }
+
+    #[test]
+    fn inactive_providers_are_valid() {
+        let headless = Headless::<Done>::new(1);
+        let sample = {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Score::default());
+            scheduler.add_task(UpdateScore);
+            scheduler.add_task(Finish);
+            scheduler.add_task(ReadSample);
+            scheduler.add_task(Sample)
+        };
+        headless.run_frames(3);
+
+        headless.scheduler().pause_task(sample);
+        headless.run_frames(2);
+        assert!(headless.task_manager().validate().is_ok());
+    }
+
+    fn node(name: &str, output: std::any::TypeId, dependencies: &[std::any::TypeId]) -> Node {
+        Node {
+            id: crate::utils::Id::new(),
+            name: String::from(name),
+            output: format!("{:?}", output),
+            output_type_id: output,
+            states: vec![],
+            locks: vec![],
+            dependencies: dependencies
+                .iter()
+                .map(|type_id| Dependency {
+                    type_id: *type_id,
+                    name: format!("{:?}", type_id),
+                    selection: Selection::Any,
+                })
+                .collect(),
+            queued: true,
+            running: false,
+            paused: false,
+        }
+    }
+
+    #[test]
+    fn validation_detects_cycles_and_lock_conflicts() {
+        use std::any::TypeId;
+
+        let mut conflicting = node("Conflicting", TypeId::of::<()>(), &[]);
+        conflicting.locks = vec![
+            (String::from("World"), Access::ReadOnly),
+            (String::from("World"), Access::ReadWrite),
+            (String::from("Assets"), Access::ReadOnly),
+            (String::from("Assets"), Access::ReadOnly),
+        ];
+
+        let graph = Graph {
+            tasks: vec![
+                node("A", TypeId::of::<u32>(), &[TypeId::of::<u64>()]),
+                node("B", TypeId::of::<u64>(), &[TypeId::of::<u32>()]),
+                node("C", TypeId::of::<u8>(), &[TypeId::of::<u64>()]),
+                conflicting,
+            ],
+            outputs: vec![OutputNode {
+                type_id: TypeId::of::<u8>(),
+                name: String::from("u8"),
+                providers: 1,
+                external: false,
+            }],
+        };
+
+        let errors = graph.validate().expect_err("Graph to be invalid");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.contains(&Error::Cycle {
+            tasks: vec![String::from("A"), String::from("B")]
+        }));
+        assert!(errors.contains(&Error::LockConflict {
+            task: String::from("Conflicting"),
+            context: String::from("World"),
+        }));
+    }
+}
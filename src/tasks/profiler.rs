@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::graph::escape;
+
+/// Timing record of a single task execution
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// Name of the task
+    pub task: String,
+    /// Number of the worker, that executed the task
+    pub worker: u32,
+    /// Number of the tasks cycle
+    pub cycle: u64,
+    /// Time of execution start since profiler creation
+    pub start: Duration,
+    /// Duration of the execution
+    pub duration: Duration,
+    /// Time between satisfaction of the task dependencies and acquiring of its locks
+    pub wait: Duration,
+    /// Task has panicked during the execution
+    pub failed: bool,
+}
+
+/// Timestamps of the task execution
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    /// Dependencies of the task were satisfied
+    pub ready: Instant,
+    /// Locks were acquired and the task was sent to a worker
+    pub dispatched: Instant,
+    /// Worker started the execution
+    pub started: Instant,
+    /// Worker finished the execution
+    pub finished: Instant,
+}
+
+struct Records {
+    list: VecDeque<Record>,
+    capacity: usize,
+}
+
+/// Tasks profiler
+///
+/// Being added to the scheduler, collects timing of every task execution. It is stored in the
+/// global context, so tasks can access it using `Ref<Profiler>`. Cloned instances share the same
+/// records.
+#[derive(Clone)]
+pub struct Profiler {
+    started: Instant,
+    records: Arc<Mutex<Records>>,
+}
+
+impl Profiler {
+    /// Constructs new profiler, keeping up to `capacity` of the latest records
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            records: Arc::new(Mutex::new(Records {
+                list: VecDeque::with_capacity(capacity),
+                capacity,
+            })),
+        }
+    }
+
+    /// Stores timing of the task execution
+    pub fn record(&self, task: &str, worker: u32, cycle: u64, timing: Timing) {
+        self.push(task, worker, cycle, timing, false);
+    }
+
+    /// Stores timing of the task execution, that has panicked
+    pub fn record_failure(&self, task: &str, worker: u32, cycle: u64, timing: Timing) {
+        self.push(task, worker, cycle, timing, true);
+    }
+
+    fn push(&self, task: &str, worker: u32, cycle: u64, timing: Timing, failed: bool) {
+        let record = Record {
+            task: String::from(task),
+            worker,
+            cycle,
+            start: timing.started.saturating_duration_since(self.started),
+            duration: timing.finished.saturating_duration_since(timing.started),
+            wait: timing.dispatched.saturating_duration_since(timing.ready),
+            failed,
+        };
+        let mut records = self.records.lock().expect("Mutex to be locked");
+        if records.capacity == 0 {
+            return;
+        }
+        if records.list.len() == records.capacity {
+            records.list.pop_front();
+        }
+        records.list.push_back(record);
+    }
+
+    /// Returns copy of collected records
+    pub fn records(&self) -> Vec<Record> {
+        self.records
+            .lock()
+            .expect("Mutex to be locked")
+            .list
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Removes all collected records
+    pub fn clear(&self) {
+        self.records
+            .lock()
+            .expect("Mutex to be locked")
+            .list
+            .clear();
+    }
+
+    /// Serializes collected records into Chrome `trace_event` JSON format
+    pub fn to_chrome_trace(&self) -> String {
+        let records = self.records();
+        let mut workers = records
+            .iter()
+            .map(|record| record.worker)
+            .collect::<Vec<_>>();
+        workers.sort_unstable();
+        workers.dedup();
+
+        let mut events = workers
+            .into_iter()
+            .map(|worker| {
+                format!(
+                    concat!(
+                        "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},",
+                        "\"args\":{{\"name\":\"dotrix::worker[{}]\"}}}}"
+                    ),
+                    worker,
+                    worker + 1
+                )
+            })
+            .collect::<Vec<_>>();
+
+        events.extend(records.iter().map(|record| {
+            format!(
+                concat!(
+                    "{{\"name\":\"{}\",\"cat\":\"task\",\"ph\":\"X\",\"ts\":{},\"dur\":{},",
+                    "\"pid\":1,\"tid\":{},\"args\":{{\"cycle\":{},\"wait_us\":{},",
+                    "\"failed\":{}}}}}"
+                ),
+                escape(&record.task),
+                record.start.as_micros(),
+                record.duration.as_micros(),
+                record.worker,
+                record.cycle,
+                record.wait.as_micros(),
+                record.failed
+            )
+        }));
+
+        format!(
+            "{{\"traceEvents\":[{}],\"displayTimeUnit\":\"ms\"}}",
+            events.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profiler;
+    use crate::graphics::Frame;
+    use crate::headless::Headless;
+    use crate::tasks::{Any, FailurePolicy, OutputChannel, Task};
+
+    struct Done;
+
+    struct Finish;
+
+    impl Task for Finish {
+        type Context = (Any<Frame>,);
+        type Output = Done;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            Done
+        }
+    }
+
+    struct Fragile;
+
+    impl Task for Fragile {
+        type Context = (Any<Frame>,);
+        type Output = ();
+
+        fn failure_policy(&self) -> FailurePolicy {
+            FailurePolicy::Retry
+        }
+
+        fn run(&mut self, (frame,): Self::Context) -> Self::Output {
+            if frame.number == 2 {
+                panic!("fragile task has failed");
+            }
+        }
+    }
+
+    #[test]
+    fn can_profile_tasks() {
+        let profiler = Profiler::new(16);
+        let headless = Headless::<Done>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_profiler(profiler.clone());
+            scheduler.add_task(Finish);
+        }
+        headless.run_frames(3);
+
+        let records = profiler.records();
+        assert_eq!(records.len(), 6);
+        let finish = records
+            .iter()
+            .filter(|record| record.task.ends_with("Finish"))
+            .collect::<Vec<_>>();
+        assert_eq!(finish.len(), 3);
+        assert_eq!(finish[2].cycle, 3);
+        assert!(finish[0].duration >= std::time::Duration::from_millis(1));
+        assert!(records.iter().all(|record| !record.failed));
+
+        let trace = profiler.to_chrome_trace();
+        assert!(trace.starts_with("{\"traceEvents\":["));
+        assert!(trace.contains("\"ph\":\"X\""));
+        assert!(trace.contains("\"name\":\"thread_name\""));
+
+        profiler.clear();
+        assert!(profiler.records().is_empty());
+    }
+
+    #[test]
+    fn can_profile_failed_tasks() {
+        let profiler = Profiler::new(16);
+        let headless = Headless::<Done>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_profiler(profiler.clone());
+            scheduler.add_task(Finish);
+            scheduler.add_task(Fragile);
+        }
+        headless.run_frames(3);
+
+        let (failed, succeeded) = profiler
+            .records()
+            .into_iter()
+            .filter(|record| record.task.ends_with("Fragile"))
+            .partition::<Vec<_>, _>(|record| record.failed);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].cycle, 2);
+        assert_eq!(succeeded[0].cycle, 1);
+        assert!(profiler.to_chrome_trace().contains("\"failed\":true"));
+    }
+}
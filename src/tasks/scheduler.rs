@@ -1,10 +1,15 @@
 use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
+use futures::channel::mpsc::UnboundedSender;
+
+use crate::graphics::Frame;
 use crate::utils::{Id, TypeLock};
 
-use super::{context, task};
+use super::{context, errors, executor, graph, profiler, task};
 
 /// Local synonym for boxified task
 pub type Task = Box<dyn task::Executable>;
@@ -13,16 +18,44 @@ pub type Task = Box<dyn task::Executable>;
 pub enum Message {
     /// Schedule a task
     Schedule(Task),
+    /// Remove a task
+    RemoveTask(Id<task::Slot>),
+    /// Pause a task
+    PauseTask(Id<task::Slot>),
+    /// Resume a paused task
+    ResumeTask(Id<task::Slot>),
+    /// Replace a task with another one with the same Id
+    ReplaceTask(Task),
     /// Complete task report
-    Output(Task, Box<dyn Any + 'static + Send>),
+    Output(Task, Box<dyn Any + 'static + Send>, Execution),
+    /// Panicked task report with the panic message
+    Failure(Task, String, Execution),
+    /// Panicked future of an async task report
+    AsyncFailure(Id<task::Slot>, TypeId, errors::Failure),
+    /// Report of the loop, abandoned due to failures of tasks
+    Abandon(errors::Abandoned),
+    /// Report of the failure, that must abort the application
+    Abort(errors::Failure),
     /// Store a new global context
     Store(TypeId, Box<dyn Any + 'static + Send>),
+    /// Store a new global context, that is updated on every loop
+    StoreUpdated(TypeId, Box<dyn Any + 'static + Send>, context::Update),
     /// Push state for the global context
-    PushState(TypeId, String, Box<dyn Any + 'static + Send>),
+    PushState(context::StateSlot),
     /// Register dependency type (with Name)
     Register(TypeId, String, usize),
     /// Provide dependency data for tasks
     Provide(TypeId, Box<dyn Any + 'static + Send>),
+    /// Enable fixed-rate ticks with specified time step
+    FixedTimestep(Duration),
+    /// Request snapshot of the tasks graph
+    Inspect(mpsc::Sender<graph::Graph>),
+    /// Report errors of the tasks graph
+    Invalid(Vec<graph::Error>),
+    /// Enable profiling of tasks
+    Profile(profiler::Profiler),
+    /// Shutdown signal with number of workers
+    Shutdown(usize),
     /// Kill Signal
     Kill(usize),
 }
@@ -31,17 +64,107 @@ pub enum Message {
 #[derive(Default)]
 pub struct Loop;
 
+/// Execution report of a worker
+pub struct Execution {
+    /// Worker number
+    pub worker: u32,
+    /// Execution start
+    pub started: Instant,
+    /// Execution end
+    pub finished: Instant,
+}
+
+/// Maximal number of fixed ticks per loop, the rest of accumulated time is dropped
+const MAX_FIXED_TICKS_PER_LOOP: u32 = 8;
+
+/// Fixed-rate simulation tick
+///
+/// Provided by the scheduler zero or several times per loop, depending on the delta of the
+/// [`Frame`] provided in the previous loop, or on the time passed since the previous loop, if
+/// there was no frame. Tasks depending on `Any<FixedTick>` run once per tick.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTick {
+    /// Fixed time step
+    pub delta: Duration,
+    /// Absolute tick number
+    pub number: u64,
+}
+
+/// Fixed-rate simulation summary, provided once per loop
+#[derive(Debug, Clone, Copy)]
+pub struct FixedUpdate {
+    /// Number of ticks provided in the current loop
+    pub ticks: u32,
+    /// Fixed time step
+    pub delta: Duration,
+    /// Interpolation factor between the last and the next tick in range `[0.0, 1.0)`
+    pub alpha: f32,
+}
+
+/// Accumulator of time for fixed-rate ticks
+struct FixedClock {
+    delta: Duration,
+    accumulator: Duration,
+    timestamp: Option<Instant>,
+    counter: u64,
+    ticks: u32,
+}
+
+impl FixedClock {
+    fn new(delta: Duration) -> Self {
+        Self {
+            delta,
+            accumulator: Duration::ZERO,
+            timestamp: None,
+            counter: 0,
+            ticks: 0,
+        }
+    }
+
+    /// Accumulates delta of the last frame or time passed since last update and returns number
+    /// of ticks to be provided
+    fn update(&mut self, now: Instant, frame_delta: Option<Duration>) -> FixedUpdate {
+        if let Some(timestamp) = self.timestamp.replace(now) {
+            self.accumulator +=
+                frame_delta.unwrap_or_else(|| now.saturating_duration_since(timestamp));
+        }
+
+        let delta = self.delta.as_nanos();
+        let steps = self.accumulator.as_nanos() / delta;
+        self.accumulator = Duration::from_nanos((self.accumulator.as_nanos() % delta) as u64);
+        let ticks = steps.min(MAX_FIXED_TICKS_PER_LOOP as u128) as u32;
+        self.ticks = ticks;
+
+        FixedUpdate {
+            ticks,
+            delta: self.delta,
+            alpha: self.accumulator.as_secs_f32() / self.delta.as_secs_f32(),
+        }
+    }
+
+    /// Returns next tick
+    fn tick(&mut self) -> FixedTick {
+        self.counter += 1;
+        FixedTick {
+            delta: self.delta,
+            number: self.counter,
+        }
+    }
+}
+
 /// Launches operator thread, that schedules tasks, holds up context and communicates back
 /// to main process
 ///
 /// input_rx -> recieve requests from main process and workers
 /// worker_tx -> send commands to workers
 /// control_tx -> response to control requests to main process
+/// executor_tx -> send futures of async tasks to executor
 pub fn spawn<T: context::Context>(
     context_manager: Arc<Mutex<context::Manager>>,
     input_rx: mpsc::Receiver<Message>,
     worker_tx: mpsc::Sender<Message>,
     control_tx: mpsc::Sender<Message>,
+    executor_tx: UnboundedSender<executor::Job>,
 ) -> thread::JoinHandle<()> {
     // tasks pool
     let name = String::from("dotrix::scheduler");
@@ -51,8 +174,34 @@ pub fn spawn<T: context::Context>(
     let mut pool = task::Pool::new();
     // tasks selected for execution
     let mut queue: Vec<Id<task::Slot>> = vec![];
+    // tasks, that must not be executed before the queued ones according to ordering constraints
+    let mut predecessors: HashMap<Id<task::Slot>, Vec<Id<task::Slot>>> = HashMap::new();
     // flag controls change of the tasks graph
     let mut tasks_graph_changed = true;
+    // flag controls change of the number of providers, when the tasks graph stays the same
+    let mut providers_changed = false;
+    // state, the tasks graph was validated for
+    let mut validated_state: Option<TypeId> = None;
+    // clock of fixed-rate ticks, if enabled
+    let mut fixed_clock: Option<FixedClock> = None;
+    // delta of the frame provided in the current loop, feeds the fixed clock
+    let mut frame_delta: Option<Duration> = None;
+    // tasks profiler, if enabled
+    let mut profiler: Option<profiler::Profiler> = None;
+    // timestamps of tasks readiness and dispatching with the cycle of execution for the profiler
+    let mut timings: HashMap<Id<task::Slot>, (Instant, Instant, u64)> = HashMap::new();
+    // counter of tasks cycles
+    let mut cycle: u64 = 0;
+    // number of tasks being executed by workers
+    let mut running: usize = 0;
+    // number of workers to be killed on shutdown, if requested
+    let mut shutdown: Option<usize> = None;
+    // failures of tasks, shared with the global context
+    let errors = errors::Errors::default();
+    // flag controls failures of tasks in the current loop
+    let mut failed = false;
+    // changes of tasks, postponed until the current loop is executed
+    let mut postponed: VecDeque<Message> = VecDeque::new();
 
     context_manager
         .lock()
@@ -63,6 +212,24 @@ pub fn spawn<T: context::Context>(
             1,
             false,
         );
+    context_manager
+        .lock()
+        .expect("Mutex to be locked")
+        .set_scheduled_provisions(std::any::TypeId::of::<Loop>(), 1);
+    context_manager
+        .lock()
+        .expect("Mutex to be locked")
+        .store_as(errors.clone());
+    // errors of fallible tasks are kept until taken
+    context_manager
+        .lock()
+        .expect("Mutex to be locked")
+        .register(
+            TypeId::of::<errors::TaskError>(),
+            std::any::type_name::<errors::TaskError>().into(),
+            0,
+            true,
+        );
 
     thread::Builder::new()
         .name(name)
@@ -71,49 +238,235 @@ pub fn spawn<T: context::Context>(
             let mut restart_queue = false;
             let mut queue_executed = true;
             loop {
-                let mut command = if lock_for_input {
+                let mut command = if queue_executed && !postponed.is_empty() {
+                    postponed.pop_front()
+                } else if lock_for_input {
                     // There is nothing else to do, except for waiting
                     Some(input_rx.recv().expect("Message to be received"))
                 } else {
                     input_rx.try_recv().map(Some).unwrap_or(None)
                 };
                 if let Some(command) = command.take() {
+                    if !queue_executed
+                        && matches!(
+                            command,
+                            Message::RemoveTask(_)
+                                | Message::PauseTask(_)
+                                | Message::ResumeTask(_)
+                                | Message::ReplaceTask(_)
+                        )
+                    {
+                        // providers of the running loop are already counted
+                        postponed.push_back(command);
+                        lock_for_input = false;
+                        continue;
+                    }
                     match command {
                         Message::Schedule(task) => {
-                            context_manager.lock().unwrap().register_provider(&task);
+                            register_provider(&mut context_manager.lock().unwrap(), &task);
                             pool.store(task);
                             tasks_graph_changed = true;
                         }
-                        Message::Output(task, data) => {
+                        Message::RemoveTask(task_id) => {
+                            if pool.remove(&task_id) {
+                                queue.retain(|id| *id != task_id);
+                                tasks_graph_changed = true;
+                            } else {
+                                log::warn!("Could not remove {:?}: no such task", task_id);
+                            }
+                        }
+                        Message::PauseTask(task_id) => {
+                            if pool.set_paused(&task_id, true) {
+                                queue.retain(|id| *id != task_id);
+                                tasks_graph_changed = true;
+                            } else {
+                                log::warn!("Could not pause {:?}: no such task", task_id);
+                            }
+                        }
+                        Message::ResumeTask(task_id) => {
+                            if pool.set_paused(&task_id, false) {
+                                tasks_graph_changed = true;
+                            } else {
+                                log::warn!("Could not resume {:?}: no such task", task_id);
+                            }
+                        }
+                        Message::ReplaceTask(task) => {
+                            let task_id = task.id();
+                            if pool.has_task(task_id) {
+                                register_provider(&mut context_manager.lock().unwrap(), &task);
+                                pool.replace(task);
+                                // replacement runs starting from the next loop
+                                queue.retain(|id| *id != task_id);
+                                tasks_graph_changed = true;
+                            } else {
+                                log::warn!("Could not replace {:?}: no such task", task_id);
+                            }
+                        }
+                        Message::Output(task, data, execution) => {
+                            running = running.saturating_sub(1);
+                            if let Some(profiler) = profiler.as_ref() {
+                                if let Some((ready, dispatched, cycle)) = timings.remove(&task.id())
+                                {
+                                    let timing = profiler::Timing {
+                                        ready,
+                                        dispatched,
+                                        started: execution.started,
+                                        finished: execution.finished,
+                                    };
+                                    profiler.record(task.name(), execution.worker, cycle, timing);
+                                }
+                            }
                             let type_id = task.output_type_id();
                             let output_channel = task.output_channel();
+                            let task_id = task.id();
+                            // failure of the future is reported by the executor
+                            let failure = errors::Failure {
+                                task: String::from(task.name()),
+                                message: String::new(),
+                                cycle,
+                                policy: task.failure_policy(),
+                            };
                             lock_manager.unlock(task.lock());
                             pool.store(task);
 
-                            match output_channel {
-                                task::OutputChannel::Pool => {
-                                    context_manager.lock().unwrap().provide(type_id, data);
+                            let data = match data.downcast::<task::Outcome>() {
+                                Ok(outcome) => match *outcome {
+                                    task::Outcome::Ok(data) => Some(data),
+                                    task::Outcome::Err(error) => {
+                                        log::warn!("{}", error);
+                                        let mut ctx = context_manager.lock().unwrap();
+                                        // dependent tasks don't wait for the output
+                                        ctx.withdraw_provider(type_id);
+                                        ctx.provide(
+                                            TypeId::of::<errors::TaskError>(),
+                                            Box::new(error),
+                                        );
+                                        providers_changed = true;
+                                        if type_id == TypeId::of::<T>() {
+                                            queue_executed = true;
+                                            control_tx.send(abandon(&errors, cycle)).ok();
+                                        } else {
+                                            failed = true;
+                                        }
+                                        None
+                                    }
+                                },
+                                Err(data) => Some(data),
+                            };
+                            if let Some(data) = data {
+                                if let Some(frame) = data.downcast_ref::<Frame>() {
+                                    frame_delta = Some(frame.delta);
                                 }
-                                task::OutputChannel::Scheduler => {
-                                    control_tx.send(Message::Provide(type_id, data)).ok();
+                                match output_channel {
+                                    task::OutputChannel::Pool => {
+                                        context_manager.lock().unwrap().provide(type_id, data);
+                                    }
+                                    task::OutputChannel::Scheduler => {
+                                        control_tx.send(Message::Provide(type_id, data)).ok();
+                                    }
+                                    task::OutputChannel::Executor => {
+                                        match data.downcast::<executor::Pending>() {
+                                            Ok(future) => {
+                                                let job = executor::Job {
+                                                    task: task_id,
+                                                    type_id,
+                                                    future: *future,
+                                                    failure,
+                                                };
+                                                executor_tx.unbounded_send(job).ok();
+                                            }
+                                            Err(_) => {
+                                                log::error!("Output of async task is not a future")
+                                            }
+                                        }
+                                    }
+                                };
+
+                                if type_id == TypeId::of::<T>() {
+                                    queue_executed = true;
                                 }
+                            }
+                        }
+                        Message::Failure(task, message, execution) => {
+                            running = running.saturating_sub(1);
+                            if let Some(profiler) = profiler.as_ref() {
+                                if let Some((ready, dispatched, cycle)) = timings.remove(&task.id())
+                                {
+                                    let timing = profiler::Timing {
+                                        ready,
+                                        dispatched,
+                                        started: execution.started,
+                                        finished: execution.finished,
+                                    };
+                                    profiler.record_failure(
+                                        task.name(),
+                                        execution.worker,
+                                        cycle,
+                                        timing,
+                                    );
+                                }
+                            }
+                            let task_id = task.id();
+                            let type_id = task.output_type_id();
+                            let failure = errors::Failure {
+                                task: String::from(task.name()),
+                                message,
+                                cycle,
+                                policy: task.failure_policy(),
                             };
+                            log::error!("{} (worker #{})", failure, execution.worker);
+                            lock_manager.unlock(task.lock());
+                            pool.store(task);
+
+                            // failed task is not executed again in this loop
+                            queue.retain(|id| *id != task_id);
+                            if failure.policy == errors::FailurePolicy::Disable {
+                                pool.set_paused(&task_id, true);
+                                tasks_graph_changed = true;
+                            }
+                            // dependent tasks don't wait for the output, that won't be provided
+                            context_manager.lock().unwrap().withdraw_provider(type_id);
+                            providers_changed = true;
+
+                            if failure.policy == errors::FailurePolicy::Abort {
+                                control_tx.send(Message::Abort(failure.clone())).ok();
+                            }
+                            errors.record(failure);
 
                             if type_id == TypeId::of::<T>() {
                                 queue_executed = true;
+                                control_tx.send(abandon(&errors, cycle)).ok();
+                            } else {
+                                failed = true;
                             }
                         }
+                        Message::AsyncFailure(task_id, type_id, failure) => {
+                            log::error!("{} (executor)", failure);
+                            if failure.policy == errors::FailurePolicy::Disable {
+                                pool.set_paused(&task_id, true);
+                                tasks_graph_changed = true;
+                            }
+                            // dependent tasks don't wait for the output, that won't be provided
+                            context_manager.lock().unwrap().withdraw_provider(type_id);
+                            providers_changed = true;
+
+                            if failure.policy == errors::FailurePolicy::Abort {
+                                control_tx.send(Message::Abort(failure.clone())).ok();
+                            }
+                            errors.record(failure);
+                        }
                         Message::Store(type_id, ctx) => {
                             context_manager.lock().unwrap().store_boxed(type_id, ctx);
                         }
-                        Message::PushState(id, name, data) => context_manager
-                            .lock()
-                            .unwrap()
-                            .push_state_slot(context::StateSlot {
-                                id,
-                                name,
-                                data: data.into(),
-                            }),
+                        Message::StoreUpdated(type_id, ctx, update) => {
+                            context_manager
+                                .lock()
+                                .unwrap()
+                                .store_updated(type_id, ctx, update);
+                        }
+                        Message::PushState(state) => {
+                            context_manager.lock().unwrap().push_state_slot(state)
+                        }
                         Message::Register(type_id, name, providers) => {
                             context_manager
                                 .lock()
@@ -123,11 +476,46 @@ pub fn spawn<T: context::Context>(
 
                         Message::Provide(type_id, data) => {
                             if type_id == TypeId::of::<Loop>() {
-                                restart_queue = true;
+                                // no new loops are started on shutdown
+                                restart_queue = shutdown.is_none();
                             } else {
                                 context_manager.lock().unwrap().provide(type_id, data);
                             }
                         }
+                        Message::FixedTimestep(delta) => {
+                            let mut ctx = context_manager.lock().unwrap();
+                            for (type_id, name) in [
+                                (
+                                    TypeId::of::<FixedTick>(),
+                                    std::any::type_name::<FixedTick>(),
+                                ),
+                                (
+                                    TypeId::of::<FixedUpdate>(),
+                                    std::any::type_name::<FixedUpdate>(),
+                                ),
+                            ] {
+                                ctx.register(type_id, name.into(), 0, false);
+                                ctx.set_scheduled_provisions(type_id, 0);
+                            }
+                            fixed_clock = Some(FixedClock::new(delta));
+                            tasks_graph_changed = true;
+                        }
+                        Message::Inspect(graph_tx) => {
+                            let ctx = context_manager.lock().unwrap();
+                            graph_tx.send(pool.graph(&queue, &ctx)).ok();
+                        }
+                        Message::Profile(instance) => {
+                            context_manager.lock().unwrap().store_as(instance.clone());
+                            profiler = Some(instance);
+                        }
+                        Message::Invalid(_) | Message::Abandon(_) | Message::Abort(_) => {
+                            // reports are sent to the main process only
+                        }
+                        Message::Shutdown(workers) => {
+                            log::info!("shutdown requested");
+                            shutdown = Some(workers);
+                            restart_queue = false;
+                        }
                         Message::Kill(workers) => {
                             for i in 0..workers {
                                 log::info!("sending kill comand to worker {i}");
@@ -148,46 +536,123 @@ pub fn spawn<T: context::Context>(
                     // log::debug!("restart queue(queue_executed: {}", queue_executed);
                     if queue_executed {
                         let mut ctx = context_manager.lock().expect("Mutex to be locked");
-                        ctx.reset_data(tasks_graph_changed);
+                        let fixed_update = fixed_clock.as_mut().map(|clock| {
+                            let last_ticks = clock.ticks;
+                            let fixed_update = clock.update(Instant::now(), frame_delta.take());
+                            if fixed_update.ticks != last_ticks {
+                                // number of ticks affects number of providers of dependent tasks
+                                providers_changed = true;
+                            }
+                            fixed_update
+                        });
                         ctx.apply_states_changes();
-                        queue.clear();
-                        ctx.provide(TypeId::of::<Loop>(), Box::new(Loop));
 
                         let default_state = TypeId::of::<()>();
                         let current_state = ctx.current_state();
                         // log::debug!("current state: {:?}", current_state);
+                        let mut candidates = vec![];
                         if current_state != default_state {
                             if let Some(tasks) = pool.select_for_state(&default_state) {
-                                queue.extend_from_slice(tasks);
+                                candidates.extend_from_slice(tasks);
                             }
                         }
                         if let Some(tasks) = pool.select_for_state(&current_state) {
-                            queue.extend_from_slice(tasks);
+                            candidates.extend_from_slice(tasks);
+                        }
+                        if validated_state != Some(current_state) {
+                            // state selects another set of tasks
+                            tasks_graph_changed = true;
                         }
+                        let previous_queue = queue.drain(..).collect::<HashSet<_>>();
+                        let now = Instant::now();
+                        for task_id in candidates.iter().copied() {
+                            if !pool.is_paused(&task_id)
+                                && pool.check_conditions(&task_id, &ctx, &mut lock_manager, now)
+                            {
+                                queue.push(task_id);
+                            }
+                        }
+                        if queue.len() != previous_queue.len()
+                            || queue.iter().any(|id| !previous_queue.contains(id))
+                        {
+                            // set of tasks affects number of providers
+                            providers_changed = true;
+                        }
+
+                        if ctx.schedule_transitions() {
+                            // number of transitions affects number of providers
+                            providers_changed = true;
+                        }
+
+                        ctx.reset_data(tasks_graph_changed || providers_changed);
+                        ctx.provide(TypeId::of::<Loop>(), Box::new(Loop));
+                        ctx.provide_transitions();
+                        ctx.update_globals();
+                        cycle += 1;
 
+                        if let Some(fixed_update) = fixed_update {
+                            let clock = fixed_clock.as_mut().expect("Fixed clock to be set");
+                            ctx.set_scheduled_provisions(
+                                TypeId::of::<FixedTick>(),
+                                fixed_update.ticks as usize,
+                            );
+                            ctx.set_scheduled_provisions(TypeId::of::<FixedUpdate>(), 1);
+                            for _ in 0..fixed_update.ticks {
+                                ctx.provide(TypeId::of::<FixedTick>(), Box::new(clock.tick()));
+                            }
+                            ctx.provide(TypeId::of::<FixedUpdate>(), Box::new(fixed_update));
+                        }
+
+                        predecessors = pool.order(&mut queue);
                         pool.reset_tasks(&queue);
 
-                        if tasks_graph_changed {
+                        if tasks_graph_changed || providers_changed {
                             unsafe {
                                 // TODO: move completely to the Pool
                                 ctx.calculate_providers::<T>(&pool, &queue);
                             }
+                            providers_changed = false;
+                        }
+                        if tasks_graph_changed {
+                            // tasks skipped by conditions are validated as well
+                            let selected = candidates
+                                .into_iter()
+                                .filter(|task_id| !pool.is_paused(task_id))
+                                .collect::<Vec<_>>();
+                            if let Err(errors) = pool.graph(&selected, &ctx).validate() {
+                                control_tx.send(Message::Invalid(errors)).ok();
+                            }
+                            validated_state = Some(current_state);
                             tasks_graph_changed = false;
                         }
                         queue_executed = false;
                         restart_queue = false;
+                        failed = false;
                     }
                 }
 
                 // execute tasks
                 let mut index = 0;
+                let mut dispatched = false;
                 let mut stop_index = queue.len();
+                // tasks, that are blocked by their predecessors in this pass
+                let mut blocked = HashSet::new();
                 // let instant = std::time::Instant::now();
                 while index < stop_index {
                     let task_id = queue[index];
                     if let Some(mut task) = pool.take(&task_id) {
                         // log::debug!("task({}): begin control", task.name());
-                        if !task.is_scheduled() {
+                        let is_blocked = predecessors
+                            .get(&task_id)
+                            .map(|ids| {
+                                ids.iter()
+                                    .any(|id| blocked.contains(id) || pool.is_pending(id))
+                            })
+                            .unwrap_or(false);
+                        if is_blocked {
+                            blocked.insert(task_id);
+                        }
+                        if !task.is_scheduled() && !is_blocked {
                             // log::debug!("task({}): not scheduled yet", task.name());
                             if task.name() == "dotrix::window::input::ReadInput" {
                                 // log::debug!(
@@ -196,13 +661,28 @@ pub fn spawn<T: context::Context>(
                                 //    task.dependencies()
                                 // );
                             }
-                            if let Some(dependencies_state) = context_manager
-                                .lock()
-                                .unwrap()
-                                .match_dependencies(task.dependencies())
-                            {
+                            let dependencies_state = {
+                                let ctx = context_manager.lock().unwrap();
+                                let is_provided =
+                                    task.conditions().iter().all(|condition| match condition {
+                                        task::Condition::Provided(type_id) => {
+                                            ctx.is_provided(type_id)
+                                        }
+                                        _ => true,
+                                    });
+                                if is_provided {
+                                    ctx.match_dependencies(task.dependencies())
+                                } else {
+                                    None
+                                }
+                            };
+                            if let Some(dependencies_state) = dependencies_state {
                                 // log::debug!("task({}): to be scheduled", task.name());
                                 task.schedule_with(dependencies_state);
+                                if profiler.is_some() {
+                                    let now = Instant::now();
+                                    timings.insert(task_id, (now, now, cycle));
+                                }
                             } else {
                                 // log::debug!(
                                 //    "task({}): dependencies are not sattisfied",
@@ -216,7 +696,12 @@ pub fn spawn<T: context::Context>(
                             // move to the end of queue
                             queue.remove(index);
                             queue.push(task_id);
+                            if let Some((_, dispatched, _)) = timings.get_mut(&task_id) {
+                                *dispatched = Instant::now();
+                            }
                             worker_tx.send(Message::Schedule(task)).ok();
+                            running += 1;
+                            dispatched = true;
                             stop_index -= 1;
                             continue;
                         }
@@ -225,8 +710,102 @@ pub fn spawn<T: context::Context>(
                     }
                     index += 1;
                 }
+
+                // current loop is drained, when nothing is running and nothing can be started
+                if let Some(workers) = shutdown {
+                    if running == 0 && !dispatched {
+                        pool.shutdown();
+                        context_manager
+                            .lock()
+                            .expect("Mutex to be locked")
+                            .shutdown();
+                        for i in 0..workers {
+                            log::info!("sending kill comand to worker {i}");
+                            worker_tx.send(Message::Kill(i)).ok();
+                        }
+                        return;
+                    }
+                }
+                // loop is abandoned, when a task has failed and nothing else can be started
+                if failed && !queue_executed && running == 0 && !dispatched {
+                    log::warn!("loop #{} is abandoned due to failures of tasks", cycle);
+                    queue_executed = true;
+                    control_tx.send(abandon(&errors, cycle)).ok();
+                }
                 lock_for_input = true;
             }
         })
         .expect("Thread to be spawned")
 }
+
+/// Registers the task as a provider of its output and selected transition events
+///
+/// Outputs of async tasks are provided by the executor at any time, so they are registered as
+/// protected and are kept until taken
+fn register_provider(ctx: &mut context::Manager, task: &Task) {
+    for type_id in task.transitions().iter() {
+        let name = task.type_names().get(type_id).copied().unwrap_or("UNKNOWN");
+        ctx.register_transition(*type_id, name.into());
+    }
+    if task.output_channel() == task::OutputChannel::Executor {
+        ctx.register(task.output_type_id(), task.output_as_str().into(), 0, true);
+    } else {
+        ctx.register_provider(task);
+    }
+}
+
+/// Reports the loop as abandoned with failures of tasks recorded in it
+fn abandon(errors: &errors::Errors, cycle: u64) -> Message {
+    Message::Abandon(errors::Abandoned {
+        cycle,
+        failures: errors
+            .failures()
+            .into_iter()
+            .filter(|failure| failure.cycle == cycle)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::FixedClock;
+
+    #[test]
+    fn fixed_clock_accumulates_ticks() {
+        let delta = Duration::from_millis(10);
+        let mut clock = FixedClock::new(delta);
+        let start = Instant::now();
+
+        assert_eq!(clock.update(start, None).ticks, 0);
+
+        let update = clock.update(start + Duration::from_millis(25), None);
+        assert_eq!(update.ticks, 2);
+        assert!((update.alpha - 0.5).abs() < 0.001);
+
+        let update = clock.update(start + Duration::from_millis(30), None);
+        assert_eq!(update.ticks, 1);
+        assert!(update.alpha < 0.001);
+
+        let update = clock.update(start + Duration::from_millis(34), None);
+        assert_eq!(update.ticks, 0);
+
+        let update = clock.update(start + Duration::from_secs(10), None);
+        assert_eq!(update.ticks, super::MAX_FIXED_TICKS_PER_LOOP);
+
+        // delta of the frame takes precedence over the wall clock
+        let update = clock.update(start + Duration::from_secs(3600), Some(delta * 2));
+        assert_eq!(update.ticks, 2);
+
+        assert_eq!(clock.tick().number, 1);
+        assert_eq!(clock.tick().number, 2);
+
+        // steps are counted at once, not iterated
+        let mut clock = FixedClock::new(Duration::from_nanos(1));
+        clock.update(start, None);
+        let update = clock.update(start + Duration::from_secs(3600), None);
+        assert_eq!(update.ticks, super::MAX_FIXED_TICKS_PER_LOOP);
+        assert_eq!(update.alpha, 0.0);
+    }
+}
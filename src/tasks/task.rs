@@ -1,11 +1,15 @@
 use std::any::{type_name, Any, TypeId};
 use std::collections::HashMap;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::log;
-use crate::tasks::context;
-use crate::utils::{Id, Lock};
+use crate::tasks::{context, errors, executor, graph};
+use crate::utils::{Id, Lock, TypeLock};
 
 /// Task output channel, defines recipient of the output
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -14,6 +18,111 @@ pub enum OutputChannel {
     Pool,
     /// Send result to scheduler
     Scheduler,
+    /// Send future of the result to executor, used by async tasks
+    Executor,
+}
+
+/// Target of a task ordering constraint
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Order {
+    /// Task of specified type
+    Task(TypeId),
+    /// Tasks marked with specified label
+    Label(&'static str),
+}
+
+impl Order {
+    /// Constructs constraint target for a task of type `T`
+    pub fn task<T: 'static>() -> Self {
+        Order::Task(TypeId::of::<T>())
+    }
+
+    /// Constructs constraint target for tasks marked with the label
+    pub fn label(label: &'static str) -> Self {
+        Order::Label(label)
+    }
+
+    /// Returns true if the task matches the target
+    fn matches(&self, ordering: &Ordering) -> bool {
+        match self {
+            Order::Task(type_id) => ordering.type_id == *type_id,
+            Order::Label(label) => ordering.labels.contains(label),
+        }
+    }
+}
+
+/// Predicate over global context
+type Predicate = Arc<dyn Fn(&context::Manager) -> bool + Send + Sync>;
+
+/// Run condition of a task
+///
+/// Conditions of frequency and predicates are evaluated once, when loop begins, so the task is
+/// not queued in the loop at all. Condition of provision holds the task in the queue until the
+/// output is provided.
+#[derive(Clone)]
+pub enum Condition {
+    /// Run once per specified number of loops
+    EveryLoops(u64),
+    /// Run at most with specified frequency in Hz
+    MaxRate(f32),
+    /// Run only if output of the type was provided in the current loop
+    Provided(TypeId),
+    /// Run only if the predicate over the global context of the type holds
+    Predicate(TypeId, Predicate),
+}
+
+impl Condition {
+    /// Constructs condition to run once per specified number of loops
+    pub fn every(loops: u64) -> Self {
+        Condition::EveryLoops(loops)
+    }
+
+    /// Constructs condition to run at most with specified frequency in Hz
+    ///
+    /// Task with a rate, that is not positive and finite, never runs
+    pub fn max_rate(hz: f32) -> Self {
+        if rate_period(hz).is_none() {
+            log::warn!("Invalid task rate {} Hz, the task will never run", hz);
+        }
+        Condition::MaxRate(hz)
+    }
+
+    /// Constructs condition to run only if output of type `T` was provided in the current loop
+    pub fn provided<T: context::Context>() -> Self {
+        Condition::Provided(TypeId::of::<T>())
+    }
+
+    /// Constructs condition to run only if the predicate over `Ref<T>` holds
+    pub fn when<T, F>(predicate: F) -> Self
+    where
+        T: context::Context,
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        Condition::Predicate(
+            TypeId::of::<T>(),
+            Arc::new(move |manager| unsafe { manager.global::<T>() }.is_some_and(&predicate)),
+        )
+    }
+}
+
+/// Returns period of the rate in Hz, if the rate is valid
+fn rate_period(hz: f32) -> Option<Duration> {
+    if hz > 0.0 && hz.is_finite() {
+        Duration::try_from_secs_f32(1.0 / hz).ok()
+    } else {
+        None
+    }
+}
+
+impl std::fmt::Debug for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::EveryLoops(loops) => f.debug_tuple("EveryLoops").field(loops).finish(),
+            Condition::MaxRate(hz) => f.debug_tuple("MaxRate").field(hz).finish(),
+            Condition::Provided(type_id) => f.debug_tuple("Provided").field(type_id).finish(),
+            Condition::Predicate(type_id, _) => f.debug_tuple("Predicate").field(type_id).finish(),
+        }
+    }
 }
 
 /// Task abstraction
@@ -31,46 +140,228 @@ pub trait Task: 'static + Send + Sync + Sized {
         OutputChannel::Pool
     }
 
+    /// Returns labels of the task, used as targets of ordering constraints
+    fn labels(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Returns targets, that must not be executed before the task, when ready at the same time
+    fn before(&self) -> Vec<Order> {
+        Vec::new()
+    }
+
+    /// Returns targets, that must not be executed after the task, when ready at the same time
+    fn after(&self) -> Vec<Order> {
+        Vec::new()
+    }
+
+    /// Returns conditions, that all must hold for the task to run
+    fn conditions(&self) -> Vec<Condition> {
+        Vec::new()
+    }
+
+    /// Returns policy applied to the task, if it panics
+    fn failure_policy(&self) -> errors::FailurePolicy {
+        errors::FailurePolicy::default()
+    }
+
+    /// Called once on shutdown of the task manager, before the task is dropped
+    fn shutdown(&mut self) {}
+
     /// Boxifies the task to be stored in pool
-    fn boxify(mut self, id: Id<Slot>) -> Box<dyn Executable> {
-        use context::ContextSelector;
-        let task_box: TaskBox<_> = TaskBox {
+    fn boxify(self, id: Id<Slot>) -> Box<dyn Executable> {
+        let metadata = Metadata::from(&self);
+        TaskBox::boxify::<Self::Context, Self::Output>(
+            self,
             id,
-            type_id: TypeId::of::<Self>(),
-            output_type_id: TypeId::of::<Self::Output>(),
-            output_type_name: String::from(type_name::<Self::Output>()),
-            name: type_name::<Self>(),
-            lock: <Self::Context>::lock(),
-            dependencies: <Self::Context>::dependencies(),
-            states: <Self::Context>::states(),
-            dependencies_state: None,
-            output_channel: self.output_channel(),
-            run: move |context_manager, dependencies| unsafe {
-                if let Ok(manager) = context_manager.lock() {
-                    let task_context = manager.fetch::<Self::Context>(dependencies);
-
-                    let task_result = self.run(task_context);
-                    Box::new(task_result)
-                } else {
-                    panic!(
-                        "Task {} has failed to access its context",
-                        type_name::<Self>()
-                    );
-                }
+            metadata,
+            |task, context_manager, dependencies| unsafe {
+                execute::<Self, _>(context_manager, dependencies, |task_context| {
+                    Box::new(Task::run(task, task_context))
+                })
             },
+            <Self as Task>::shutdown,
+        )
+    }
+}
+
+/// Asynchronous task abstraction
+///
+/// Context of the task is accessible only in `run`, so the returned future must own all the data
+/// it needs. The future is polled by the executor thread and its output is provided to the
+/// context, when resolved, even if it happens several loops later. Such output is kept until
+/// taken, so dependent tasks should select it using `Take<Any<T>>` or `Take<All<T>>`.
+pub trait AsyncTask: 'static + Send + Sync + Sized {
+    /// Type of task's context
+    type Context: context::ContextSelector;
+    /// Type of task's output
+    type Output: 'static + Send;
+    /// Type of the future, resolving into the task's output
+    type Future: Future<Output = Self::Output> + Send + 'static;
+
+    /// Starts the task
+    fn run(&mut self, ctx: Self::Context) -> Self::Future;
+
+    /// Returns labels of the task, used as targets of ordering constraints
+    fn labels(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Returns targets, that must not be executed before the task, when ready at the same time
+    fn before(&self) -> Vec<Order> {
+        Vec::new()
+    }
+
+    /// Returns targets, that must not be executed after the task, when ready at the same time
+    fn after(&self) -> Vec<Order> {
+        Vec::new()
+    }
+
+    /// Returns conditions, that all must hold for the task to run
+    fn conditions(&self) -> Vec<Condition> {
+        Vec::new()
+    }
+
+    /// Returns policy applied to the task, if it panics
+    fn failure_policy(&self) -> errors::FailurePolicy {
+        errors::FailurePolicy::default()
+    }
+
+    /// Called once on shutdown of the task manager, before the task is dropped
+    fn shutdown(&mut self) {}
+
+    /// Boxifies the task to be stored in pool
+    fn boxify(self, id: Id<Slot>) -> Box<dyn Executable> {
+        let metadata = Metadata {
+            output_channel: OutputChannel::Executor,
+            labels: self.labels(),
+            before: self.before(),
+            after: self.after(),
+            conditions: self.conditions(),
+            failure_policy: self.failure_policy(),
         };
-        Box::new(task_box)
+        TaskBox::boxify::<Self::Context, Self::Output>(
+            self,
+            id,
+            metadata,
+            |task, context_manager, dependencies| unsafe {
+                execute::<Self, _>(context_manager, dependencies, |task_context| {
+                    let future = AsyncTask::run(task, task_context);
+                    let pending: executor::Pending = Box::pin(async move {
+                        let output = future.await;
+                        Box::new(output) as Box<dyn Any + 'static + Send>
+                    });
+                    Box::new(pending)
+                })
+            },
+            <Self as AsyncTask>::shutdown,
+        )
     }
 }
 
-/// Boxified task
-pub struct TaskBox<F>
+/// Fallible task abstraction
+///
+/// Implemented for every [`Task`] returning `Result`. `Ok` output of the task is provided to
+/// dependent tasks as usual. `Err` is converted into [`errors::TaskError`] and provided to the
+/// central error channel instead. Errors are kept until taken, so tasks consume them using
+/// `Take<All<TaskError>>`.
+pub trait FallibleTask: Task {
+    /// Type of the output, provided to dependent tasks
+    type Ok: 'static + Send;
+
+    /// Boxifies the task to be stored in pool
+    fn boxify(self, id: Id<Slot>) -> Box<dyn Executable>;
+}
+
+impl<T, O, E> FallibleTask for T
 where
-    F: FnMut(
-        &Arc<Mutex<context::Manager>>,
-        &context::Dependencies,
-    ) -> Box<dyn Any + 'static + Send>,
+    T: Task<Output = Result<O, E>>,
+    O: 'static + Send,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
 {
+    type Ok = O;
+
+    fn boxify(self, id: Id<Slot>) -> Box<dyn Executable> {
+        let metadata = Metadata::from(&self);
+        TaskBox::boxify::<Self::Context, O>(
+            self,
+            id,
+            metadata,
+            |task, context_manager, dependencies| unsafe {
+                execute::<Self, _>(context_manager, dependencies, |task_context| {
+                    Box::new(match Task::run(task, task_context) {
+                        Ok(output) => Outcome::Ok(Box::new(output)),
+                        Err(error) => Outcome::Err(errors::TaskError {
+                            task: type_name::<Self>(),
+                            error: error.into(),
+                        }),
+                    })
+                })
+            },
+            <Self as Task>::shutdown,
+        )
+    }
+}
+
+/// Result of a fallible task execution
+pub enum Outcome {
+    /// Output to be provided to dependent tasks
+    Ok(Box<dyn Any + 'static + Send>),
+    /// Error to be provided to the error channel
+    Err(errors::TaskError),
+}
+
+/// Function executing the task
+type Run<T> = fn(
+    &mut T,
+    &Arc<Mutex<context::Manager>>,
+    &context::Dependencies,
+) -> thread::Result<Box<dyn Any + 'static + Send>>;
+
+/// Fetches context of the task and executes it
+///
+/// Panic is caught before the guard is dropped, so the mutex is not poisoned
+unsafe fn execute<T, C>(
+    context_manager: &Arc<Mutex<context::Manager>>,
+    dependencies: &context::Dependencies,
+    run: impl FnOnce(C) -> Box<dyn Any + 'static + Send>,
+) -> thread::Result<Box<dyn Any + 'static + Send>>
+where
+    C: context::ContextSelector,
+{
+    if let Ok(manager) = context_manager.lock() {
+        panic::catch_unwind(AssertUnwindSafe(|| run(manager.fetch::<C>(dependencies))))
+    } else {
+        panic!("Task {} has failed to access its context", type_name::<T>());
+    }
+}
+
+/// Properties of the task, defined by its implementation
+struct Metadata {
+    output_channel: OutputChannel,
+    labels: Vec<&'static str>,
+    before: Vec<Order>,
+    after: Vec<Order>,
+    conditions: Vec<Condition>,
+    failure_policy: errors::FailurePolicy,
+}
+
+impl<T: Task> From<&T> for Metadata {
+    fn from(task: &T) -> Self {
+        Self {
+            output_channel: task.output_channel(),
+            labels: task.labels(),
+            before: task.before(),
+            after: task.after(),
+            conditions: task.conditions(),
+            failure_policy: task.failure_policy(),
+        }
+    }
+}
+
+/// Boxified task
+pub struct TaskBox<T> {
+    task: T,
     id: Id<Slot>,
     type_id: TypeId,
     output_type_id: TypeId,
@@ -79,18 +370,26 @@ where
     lock: Vec<Lock>,
     dependencies: context::Dependencies,
     states: Vec<TypeId>,
+    type_names: HashMap<TypeId, &'static str>,
+    transitions: Vec<TypeId>,
     output_channel: OutputChannel,
-    run: F,
+    labels: Vec<&'static str>,
+    before: Vec<Order>,
+    after: Vec<Order>,
+    conditions: Vec<Condition>,
+    failure_policy: errors::FailurePolicy,
+    run: Run<T>,
+    shutdown: fn(&mut T),
     dependencies_state: Option<context::Dependencies>,
 }
 
 /// Abstraction for tasks independently of function signature
 pub trait Executable: Send + Sync {
-    /// Execute task
+    /// Execute task, returns panic payload if the task has panicked
     fn run(
         &mut self,
         context_manager: &Arc<Mutex<context::Manager>>,
-    ) -> Box<dyn Any + 'static + Send>;
+    ) -> thread::Result<Box<dyn Any + 'static + Send>>;
 
     /// Get task name
     fn name(&self) -> &str;
@@ -116,6 +415,12 @@ pub trait Executable: Send + Sync {
     /// Task dependencies
     fn dependencies(&self) -> &context::Dependencies;
 
+    /// Names of types selected by the task context
+    fn type_names(&self) -> &HashMap<TypeId, &'static str>;
+
+    /// Types of state transition events selected by the task context
+    fn transitions(&self) -> &[TypeId];
+
     /// Set dependencies state for the scheduler
     fn schedule_with(&mut self, dependencies_state: context::Dependencies);
 
@@ -127,23 +432,77 @@ pub trait Executable: Send + Sync {
 
     /// Returns channel where output of the task must be provided
     fn output_channel(&self) -> OutputChannel;
+
+    /// Labels of the task
+    fn labels(&self) -> &[&'static str];
+
+    /// Targets, that must not be executed before the task
+    fn before(&self) -> &[Order];
+
+    /// Targets, that must not be executed after the task
+    fn after(&self) -> &[Order];
+
+    /// Run conditions of the task
+    fn conditions(&self) -> &[Condition];
+
+    /// Policy applied to the task, if it panics
+    fn failure_policy(&self) -> errors::FailurePolicy;
+
+    /// Shut the task down
+    fn shutdown(&mut self);
+}
+
+impl<T> TaskBox<T>
+where
+    T: 'static + Send + Sync,
+{
+    /// Boxifies the task with context `C` and output `O`
+    fn boxify<C, O>(
+        task: T,
+        id: Id<Slot>,
+        metadata: Metadata,
+        run: Run<T>,
+        shutdown: fn(&mut T),
+    ) -> Box<dyn Executable>
+    where
+        C: context::ContextSelector,
+        O: 'static,
+    {
+        Box::new(TaskBox {
+            task,
+            id,
+            type_id: TypeId::of::<T>(),
+            output_type_id: TypeId::of::<O>(),
+            output_type_name: String::from(type_name::<O>()),
+            name: type_name::<T>(),
+            lock: C::lock(),
+            dependencies: C::dependencies(),
+            states: C::states(),
+            type_names: C::type_names(),
+            transitions: C::transitions(),
+            output_channel: metadata.output_channel,
+            labels: metadata.labels,
+            before: metadata.before,
+            after: metadata.after,
+            conditions: metadata.conditions,
+            failure_policy: metadata.failure_policy,
+            run,
+            shutdown,
+            dependencies_state: None,
+        })
+    }
 }
 
-impl<F> Executable for TaskBox<F>
+impl<T> Executable for TaskBox<T>
 where
-    F: FnMut(
-            &Arc<Mutex<context::Manager>>,
-            &context::Dependencies,
-        ) -> Box<dyn Any + 'static + Send>
-        + Send
-        + Sync,
+    T: Send + Sync,
 {
     fn run(
         &mut self,
         context_manager: &Arc<Mutex<context::Manager>>,
-    ) -> Box<dyn Any + 'static + Send> {
-        let result = (self.run)(context_manager, &self.dependencies);
+    ) -> thread::Result<Box<dyn Any + 'static + Send>> {
         let dependencies_state = self.dependencies_state.take().unwrap();
+        let result = (self.run)(&mut self.task, context_manager, &dependencies_state);
         self.dependencies = dependencies_state;
         result
     }
@@ -172,6 +531,30 @@ where
         self.output_channel
     }
 
+    fn labels(&self) -> &[&'static str] {
+        &self.labels
+    }
+
+    fn before(&self) -> &[Order] {
+        &self.before
+    }
+
+    fn after(&self) -> &[Order] {
+        &self.after
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    fn failure_policy(&self) -> errors::FailurePolicy {
+        self.failure_policy
+    }
+
+    fn shutdown(&mut self) {
+        (self.shutdown)(&mut self.task)
+    }
+
     fn lock(&self) -> &[Lock] {
         self.lock.as_slice()
     }
@@ -184,6 +567,14 @@ where
         &self.dependencies
     }
 
+    fn type_names(&self) -> &HashMap<TypeId, &'static str> {
+        &self.type_names
+    }
+
+    fn transitions(&self) -> &[TypeId] {
+        &self.transitions
+    }
+
     fn schedule_with(&mut self, dependencies_state: context::Dependencies) {
         self.dependencies_state = Some(dependencies_state);
     }
@@ -194,6 +585,8 @@ where
     }
 
     fn reset(&mut self) {
+        // data, selected in the previous loop, is not available anymore
+        self.dependencies_state = None;
         self.dependencies.reset();
     }
 }
@@ -218,10 +611,59 @@ impl<T> Default for Output<T> {
     }
 }
 
+/// Ordering constraints of the stored task
+struct Ordering {
+    type_id: TypeId,
+    labels: Vec<&'static str>,
+    before: Vec<Order>,
+    after: Vec<Order>,
+}
+
+impl From<&Box<dyn Executable>> for Ordering {
+    fn from(task: &Box<dyn Executable>) -> Self {
+        Self {
+            type_id: Executable::type_id(task.as_ref()),
+            labels: task.labels().to_vec(),
+            before: task.before().to_vec(),
+            after: task.after().to_vec(),
+        }
+    }
+}
+
+/// Run conditions of the stored task and results of their evaluation
+struct Conditions {
+    list: Vec<Condition>,
+    /// Number of loops since the last queueing
+    loops: Option<u64>,
+    /// Time, when the task can be queued again according to its maximal rate
+    next_run: Option<Instant>,
+    /// Result of the last evaluation
+    queued: bool,
+}
+
+impl From<&Box<dyn Executable>> for Conditions {
+    fn from(task: &Box<dyn Executable>) -> Self {
+        Self {
+            list: task.conditions().to_vec(),
+            loops: None,
+            next_run: None,
+            queued: true,
+        }
+    }
+}
+
 /// Memory slot for the stored task
-#[derive(Default)]
 pub struct Slot {
     task: Option<Box<dyn Executable>>,
+    node: graph::Node,
+    ordering: Ordering,
+    conditions: Conditions,
+    /// Paused task is not selected for execution
+    paused: bool,
+    /// Task was removed during its execution and must be dropped on return
+    removed: bool,
+    /// Task, that replaces the current one on return from execution
+    replacement: Option<Box<dyn Executable>>,
 }
 
 impl Slot {
@@ -260,7 +702,14 @@ impl Pool {
     pub fn store(&mut self, task: Box<dyn Executable>) {
         let task_id = task.id();
         if let Some(slot) = self.tasks.get_mut(&task_id) {
-            slot.task = Some(task);
+            if slot.removed {
+                self.tasks.remove(&task_id);
+            } else if let Some(replacement) = slot.replacement.take() {
+                slot.task = Some(task);
+                self.replace(replacement);
+            } else {
+                slot.task = Some(task);
+            }
         } else {
             let states = task.states();
             if states.is_empty() {
@@ -273,8 +722,232 @@ impl Pool {
                     self.states.entry(*state_type_id).or_default().push(task_id);
                 }
             }
-            self.tasks.insert(task_id, Slot { task: Some(task) });
+            self.tasks.insert(
+                task_id,
+                Slot {
+                    node: graph::Node::from(&task),
+                    ordering: Ordering::from(&task),
+                    conditions: Conditions::from(&task),
+                    task: Some(task),
+                    paused: false,
+                    removed: false,
+                    replacement: None,
+                },
+            );
+        }
+    }
+
+    /// Removes task from the `Pool`
+    ///
+    /// If task is being executed, it will be dropped on return. Returns false if task does not
+    /// exist
+    pub fn remove(&mut self, id: &Id<Slot>) -> bool {
+        for tasks in self.states.values_mut() {
+            tasks.retain(|task_id| task_id != id);
+        }
+        match self.tasks.get_mut(id) {
+            Some(slot) if slot.task.is_none() => {
+                slot.removed = true;
+                true
+            }
+            Some(_) => self.tasks.remove(id).is_some(),
+            None => false,
+        }
+    }
+
+    /// Replaces task with the one with the same `Id`, keeping its paused flag
+    ///
+    /// If task is being executed, it will be replaced on return. Returns false if task does not
+    /// exist
+    pub fn replace(&mut self, task: Box<dyn Executable>) -> bool {
+        let task_id = task.id();
+        let paused = match self.tasks.get_mut(&task_id) {
+            Some(slot) if slot.task.is_none() => {
+                slot.replacement = Some(task);
+                return true;
+            }
+            Some(slot) => slot.paused,
+            None => return false,
+        };
+        self.remove(&task_id);
+        self.store(task);
+        self.set_paused(&task_id, paused);
+        true
+    }
+
+    /// Sets paused flag of the task. Returns false if task does not exist
+    pub fn set_paused(&mut self, id: &Id<Slot>, paused: bool) -> bool {
+        self.tasks
+            .get_mut(id)
+            .map(|slot| slot.paused = paused)
+            .is_some()
+    }
+
+    /// Returns true if task is paused
+    pub fn is_paused(&self, id: &Id<Slot>) -> bool {
+        self.tasks.get(id).map(|slot| slot.paused).unwrap_or(false)
+    }
+
+    /// Evaluates run conditions of the task at the beginning of a loop and returns true, if the
+    /// task must be queued
+    ///
+    /// Predicate over data, that is locked for writing, is not evaluated and the result of the
+    /// previous evaluation is returned
+    pub fn check_conditions(
+        &mut self,
+        id: &Id<Slot>,
+        context: &context::Manager,
+        lock_manager: &mut TypeLock,
+        now: Instant,
+    ) -> bool {
+        let conditions = match self.tasks.get_mut(id) {
+            Some(slot) => &mut slot.conditions,
+            None => return false,
+        };
+        if conditions.list.is_empty() {
+            return true;
+        }
+
+        let loops = conditions.loops.map(|loops| loops + 1);
+        let mut queued = true;
+        for condition in conditions.list.iter() {
+            let holds = match condition {
+                Condition::EveryLoops(every) => loops.map(|loops| loops >= *every).unwrap_or(true),
+                Condition::MaxRate(hz) => {
+                    rate_period(*hz).is_some()
+                        && conditions
+                            .next_run
+                            .map(|next_run| now >= next_run)
+                            .unwrap_or(true)
+                }
+                Condition::Provided(_) => true,
+                Condition::Predicate(type_id, predicate) => {
+                    let lock = [Lock::ReadOnly(*type_id)];
+                    if !lock_manager.lock(&lock) {
+                        return conditions.queued;
+                    }
+                    let holds = predicate(context);
+                    lock_manager.unlock(&lock);
+                    holds
+                }
+            };
+            if !holds {
+                queued = false;
+                break;
+            }
+        }
+
+        if queued {
+            conditions.loops = Some(0);
+            for condition in conditions.list.iter() {
+                let period = match condition {
+                    Condition::MaxRate(hz) => rate_period(*hz),
+                    _ => None,
+                };
+                if let Some(period) = period {
+                    conditions.next_run = conditions
+                        .next_run
+                        .map(|next_run| next_run + period)
+                        .filter(|next_run| *next_run > now)
+                        .or(Some(now + period));
+                }
+            }
+        } else {
+            conditions.loops = loops;
         }
+        conditions.queued = queued;
+        queued
+    }
+
+    /// Returns true if task is ready to be executed or is being executed
+    pub fn is_pending(&self, id: &Id<Slot>) -> bool {
+        self.tasks
+            .get(id)
+            .map(|slot| {
+                !slot.removed
+                    && !slot.paused
+                    && slot
+                        .task
+                        .as_ref()
+                        .map(|task| task.is_scheduled())
+                        .unwrap_or(true)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Sorts the queue according to ordering constraints of tasks and returns predecessors of
+    /// every constrained task
+    ///
+    /// Constraints forming a cycle are reported and ignored
+    pub fn order(&self, queue: &mut Vec<Id<Slot>>) -> HashMap<Id<Slot>, Vec<Id<Slot>>> {
+        let mut predecessors: HashMap<Id<Slot>, Vec<Id<Slot>>> = HashMap::new();
+        for first_id in queue.iter() {
+            for second_id in queue.iter().filter(|id| *id != first_id) {
+                if let (Some(first), Some(second)) =
+                    (self.tasks.get(first_id), self.tasks.get(second_id))
+                {
+                    let first_is_before = first
+                        .ordering
+                        .before
+                        .iter()
+                        .any(|order| order.matches(&second.ordering))
+                        || second
+                            .ordering
+                            .after
+                            .iter()
+                            .any(|order| order.matches(&first.ordering));
+                    if first_is_before {
+                        predecessors.entry(*second_id).or_default().push(*first_id);
+                    }
+                }
+            }
+        }
+
+        if predecessors.is_empty() {
+            return predecessors;
+        }
+
+        let mut remaining = std::mem::take(queue);
+        while !remaining.is_empty() {
+            let next = remaining.iter().position(|id| {
+                predecessors
+                    .get(id)
+                    .map(|ids| ids.iter().all(|id| !remaining.contains(id)))
+                    .unwrap_or(true)
+            });
+            if let Some(index) = next {
+                queue.push(remaining.remove(index));
+            } else {
+                log::error!(
+                    "Ordering constraints of tasks form a cycle: {}",
+                    remaining
+                        .iter()
+                        .filter_map(|id| self.tasks.get(id).map(|slot| slot.node.name.as_str()))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                for id in remaining.iter() {
+                    predecessors.remove(id);
+                }
+                queue.append(&mut remaining);
+            }
+        }
+
+        predecessors
+    }
+
+    /// Calls shutdown hooks of all tasks and drops them
+    pub fn shutdown(&mut self) {
+        for task in self
+            .tasks
+            .values_mut()
+            .filter_map(|slot| slot.task.as_mut())
+        {
+            log::debug!("shutdown(task: {})", task.name());
+            task.shutdown();
+        }
+        self.tasks.clear();
+        self.states.clear();
     }
 
     /// Removes task specified by `Id` from the `Pool` and returns it
@@ -298,6 +971,43 @@ impl Pool {
         }
     }
 
+    /// Returns snapshot of the tasks graph
+    pub fn graph(&self, queue: &[Id<Slot>], context: &context::Manager) -> graph::Graph {
+        let mut tasks = self
+            .tasks
+            .values()
+            .filter(|slot| !slot.removed)
+            .map(|slot| graph::Node {
+                queued: queue.contains(&slot.node.id),
+                running: slot.task.is_none(),
+                paused: slot.paused,
+                ..slot.node.clone()
+            })
+            .collect::<Vec<_>>();
+        tasks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut outputs = tasks
+            .iter()
+            .flat_map(|node| {
+                node.dependencies
+                    .iter()
+                    .map(|dependency| (dependency.type_id, dependency.name.as_str()))
+                    .chain(std::iter::once((node.output_type_id, node.output.as_str())))
+            })
+            .collect::<HashMap<_, _>>()
+            .into_iter()
+            .map(|(type_id, name)| graph::OutputNode {
+                type_id,
+                name: String::from(context.output_name(&type_id).unwrap_or(name)),
+                providers: context.output_providers(&type_id).unwrap_or(0),
+                external: context.is_external_output(&type_id),
+            })
+            .collect::<Vec<_>>();
+        outputs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        graph::Graph { tasks, outputs }
+    }
+
     /// Calculates how many tasks will provide specified output in the queue
     pub fn calculate_context_providers(
         &self,
@@ -329,6 +1039,11 @@ impl Pool {
                         let any_providers =
                             self.calculate_context_providers(queue, *dep_type_id, context);
                         if any_providers == 0 {
+                            if context.scheduled_provisions(dep_type_id).is_some() {
+                                // scheduler provides nothing this loop, so task won't run
+                                p = 0;
+                                continue;
+                            }
                             log::warn!(
                                 "Task {} dependency on {} could be never satisfied",
                                 task.name(),
@@ -345,13 +1060,29 @@ impl Pool {
                             p *= any_providers;
                         }
                     }
-                    context::DependencyType::All(_) => {
+                    context::DependencyType::All(_) | context::DependencyType::Try(_, _) => {
                         self.calculate_context_providers(queue, *dep_type_id, context);
                     }
                 };
             }
+            for condition in task.conditions().iter() {
+                if let Condition::Provided(type_id) = condition {
+                    if self.calculate_context_providers(queue, *type_id, context) == 0
+                        && !context.is_external_output(type_id)
+                    {
+                        // output is never provided, so task won't run
+                        p = 0;
+                    }
+                }
+            }
             providers += p;
         }
+
+        if providers == 0 {
+            if let Some(provisions) = context.scheduled_provisions(&output_type_id) {
+                providers = provisions;
+            }
+        }
         log::debug!(
             "{} has {} providers",
             context.output_name(&output_type_id).unwrap_or("UNKNOWN"),
@@ -362,3 +1093,805 @@ impl Pool {
         providers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::Frame;
+    use crate::headless::Headless;
+    use crate::tasks::{
+        All, Any, AsyncTask, Condition, Errors, FailurePolicy, Mut, Order, OutputChannel, Ref,
+        Take, Task, TaskError,
+    };
+
+    #[derive(Default)]
+    struct Counter(u32);
+
+    struct Step;
+
+    struct Increment(u32);
+
+    impl Task for Increment {
+        type Context = (Any<Frame>, Mut<Counter>);
+        type Output = Step;
+
+        fn run(&mut self, (_frame, mut counter): Self::Context) -> Self::Output {
+            counter.0 += self.0;
+            Step
+        }
+    }
+
+    struct Done(u32);
+
+    struct Finish;
+
+    impl Task for Finish {
+        type Context = (All<Step>, Mut<Counter>);
+        type Output = Done;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_steps, counter): Self::Context) -> Self::Output {
+            Done(counter.0)
+        }
+    }
+
+    #[test]
+    fn can_pause_replace_and_remove_tasks() {
+        let headless = Headless::<Done>::new(2);
+        let increment = {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.add_task(Finish);
+            scheduler.add_task(Increment(1))
+        };
+        assert_eq!(headless.step().0, 1);
+        assert_eq!(headless.step().0, 2);
+
+        headless.scheduler().pause_task(increment);
+        assert_eq!(headless.step().0, 2);
+        assert!(headless
+            .task_manager()
+            .graph()
+            .tasks
+            .iter()
+            .any(|node| node.paused));
+
+        headless.scheduler().resume_task(increment);
+        assert_eq!(headless.step().0, 3);
+
+        headless.scheduler().replace_task(increment, Increment(10));
+        assert_eq!(headless.step().0, 13);
+
+        headless.scheduler().pause_task(increment);
+        headless.scheduler().replace_task(increment, Increment(100));
+        assert_eq!(headless.step().0, 13);
+        headless.scheduler().resume_task(increment);
+        assert_eq!(headless.step().0, 113);
+
+        headless.scheduler().remove_task(increment);
+        assert_eq!(headless.step().0, 113);
+        assert_eq!(headless.task_manager().graph().tasks.len(), 2);
+    }
+
+    struct Ready;
+
+    struct Slow;
+
+    impl Task for Slow {
+        type Context = (Any<Frame>,);
+        type Output = Ready;
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            Ready
+        }
+    }
+
+    struct Feed;
+
+    impl Task for Feed {
+        type Context = (Any<Ready>, Mut<Counter>);
+        type Output = Step;
+
+        fn run(&mut self, (_ready, mut counter): Self::Context) -> Self::Output {
+            counter.0 += 1;
+            Step
+        }
+    }
+
+    #[test]
+    fn can_pause_task_during_loop() {
+        let headless = Headless::<Done>::new(2);
+        let feed = {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.add_task(Finish);
+            scheduler.add_task(Slow);
+            scheduler.add_task(Feed)
+        };
+        assert_eq!(headless.step().0, 1);
+
+        headless.task_manager().run();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        headless.scheduler().pause_task(feed);
+        // pause takes effect from the next loop
+        assert_eq!(
+            headless
+                .task_manager()
+                .wait_for::<Done>()
+                .expect("Loop not to be abandoned")
+                .0,
+            2
+        );
+        assert_eq!(headless.step().0, 2);
+
+        headless.scheduler().resume_task(feed);
+        assert_eq!(headless.step().0, 3);
+    }
+
+    struct Loaded(u32);
+
+    struct Load(Option<futures::channel::oneshot::Receiver<u32>>);
+
+    impl AsyncTask for Load {
+        type Context = (Any<Frame>,);
+        type Output = Loaded;
+        type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Loaded> + Send>>;
+
+        fn run(&mut self, _: Self::Context) -> Self::Future {
+            let rx = self.0.take().expect("Task to run once");
+            Box::pin(async move { Loaded(rx.await.expect("Value to be sent")) })
+        }
+    }
+
+    #[derive(Default)]
+    struct Results(Vec<u32>);
+
+    struct Collect;
+
+    impl Task for Collect {
+        type Context = (Take<Any<Loaded>>, Mut<Results>);
+        type Output = ();
+
+        fn run(&mut self, (loaded, mut results): Self::Context) -> Self::Output {
+            results.0.push(loaded.0);
+        }
+    }
+
+    struct Collected(Vec<u32>);
+
+    struct Report;
+
+    impl Task for Report {
+        type Context = (Any<Frame>, Mut<Results>);
+        type Output = Collected;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_frame, results): Self::Context) -> Self::Output {
+            Collected(results.0.clone())
+        }
+    }
+
+    #[test]
+    fn can_provide_output_of_async_task_later() {
+        let (tx, rx) = futures::channel::oneshot::channel();
+        let headless = Headless::<Collected>::new(2);
+        let load = {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Results::default());
+            scheduler.add_task(Collect);
+            scheduler.add_task(Report);
+            scheduler.add_async_task(Load(Some(rx)))
+        };
+        assert!(headless.step().0.is_empty());
+
+        // removal of the task does not cancel its future
+        headless.scheduler().remove_task(load);
+        for _ in 0..3 {
+            assert!(headless.step().0.is_empty());
+        }
+
+        tx.send(42).expect("Value to be sent");
+        let mut collected = vec![];
+        for _ in 0..100 {
+            collected = headless.step().0;
+            if !collected.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(collected, vec![42]);
+        assert_eq!(headless.step().0, vec![42]);
+    }
+
+    #[test]
+    fn panicked_future_of_async_task_is_reported() {
+        let (tx, rx) = futures::channel::oneshot::channel::<u32>();
+        let headless = Headless::<Collected>::new(2);
+        let load = {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Results::default());
+            scheduler.add_task(Collect);
+            scheduler.add_task(Report);
+            scheduler.add_async_task(Load(Some(rx)))
+        };
+        let errors = headless
+            .task_manager()
+            .remove_global_context::<Errors>()
+            .expect("Errors to be in the context");
+        headless.step();
+        headless.scheduler().remove_task(load);
+
+        // future panics, when the sender is dropped
+        drop(tx);
+        for _ in 0..100 {
+            headless.step();
+            if !errors.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let failures = errors.failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].message, "Value to be sent: Canceled");
+        assert!(failures[0].task.ends_with("Load"));
+
+        // executor keeps running futures of other tasks
+        let (tx, rx) = futures::channel::oneshot::channel();
+        let load = headless.scheduler().add_async_task(Load(Some(rx)));
+        headless.step();
+        headless.scheduler().remove_task(load);
+        tx.send(7).expect("Value to be sent");
+        let mut collected = vec![];
+        for _ in 0..100 {
+            collected = headless.step().0;
+            if !collected.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(collected, vec![7]);
+    }
+
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    struct Logged;
+
+    struct First;
+
+    impl Task for First {
+        type Context = (Any<Frame>, Mut<Log>);
+        type Output = Logged;
+
+        fn labels(&self) -> Vec<&'static str> {
+            vec!["first"]
+        }
+
+        fn run(&mut self, (_frame, mut log): Self::Context) -> Self::Output {
+            log.0.push("first");
+            Logged
+        }
+    }
+
+    struct Second;
+
+    impl Task for Second {
+        type Context = (Any<Frame>, Mut<Log>);
+        type Output = Logged;
+
+        fn after(&self) -> Vec<Order> {
+            vec![Order::task::<First>()]
+        }
+
+        fn run(&mut self, (_frame, mut log): Self::Context) -> Self::Output {
+            log.0.push("second");
+            Logged
+        }
+    }
+
+    struct Third;
+
+    impl Task for Third {
+        type Context = (Any<Frame>, Mut<Log>);
+        type Output = Logged;
+
+        fn before(&self) -> Vec<Order> {
+            vec![Order::label("first")]
+        }
+
+        fn run(&mut self, (_frame, mut log): Self::Context) -> Self::Output {
+            log.0.push("third");
+            Logged
+        }
+    }
+
+    struct Sequence(Vec<&'static str>);
+
+    struct Flush;
+
+    impl Task for Flush {
+        type Context = (All<Logged>, Mut<Log>);
+        type Output = Sequence;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_logged, mut log): Self::Context) -> Self::Output {
+            Sequence(std::mem::take(&mut log.0))
+        }
+    }
+
+    #[test]
+    fn can_order_tasks_by_type_and_label() {
+        let headless = Headless::<Sequence>::new(4);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Log::default());
+            scheduler.add_task(Flush);
+            scheduler.add_task(Second);
+            scheduler.add_task(First);
+            scheduler.add_task(Third);
+        }
+        for _ in 0..10 {
+            assert_eq!(headless.step().0, vec!["third", "first", "second"]);
+        }
+    }
+
+    #[derive(Default, Clone, Debug, PartialEq)]
+    struct Stats {
+        every: u32,
+        throttled: u32,
+        listened: u32,
+        predicated: u32,
+    }
+
+    struct Settings {
+        enabled: bool,
+    }
+
+    struct Signal;
+
+    struct EveryThird;
+
+    impl Task for EveryThird {
+        type Context = (Any<Frame>, Mut<Stats>);
+        type Output = Step;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::every(3)]
+        }
+
+        fn run(&mut self, (_frame, mut stats): Self::Context) -> Self::Output {
+            stats.every += 1;
+            Step
+        }
+    }
+
+    struct Throttled(f32);
+
+    impl Task for Throttled {
+        type Context = (Any<Frame>, Mut<Stats>);
+        type Output = Step;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::max_rate(self.0)]
+        }
+
+        fn run(&mut self, (_frame, mut stats): Self::Context) -> Self::Output {
+            stats.throttled += 1;
+            Step
+        }
+    }
+
+    struct Emit;
+
+    impl Task for Emit {
+        type Context = (Any<Frame>,);
+        type Output = Signal;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::every(2)]
+        }
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {
+            Signal
+        }
+    }
+
+    struct Listen;
+
+    impl Task for Listen {
+        type Context = (Any<Frame>, Mut<Stats>);
+        type Output = Step;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::provided::<Signal>()]
+        }
+
+        fn run(&mut self, (_frame, mut stats): Self::Context) -> Self::Output {
+            stats.listened += 1;
+            Step
+        }
+    }
+
+    struct WhenEnabled;
+
+    impl Task for WhenEnabled {
+        type Context = (Any<Frame>, Mut<Stats>);
+        type Output = Step;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::when::<Settings, _>(|settings| settings.enabled)]
+        }
+
+        fn run(&mut self, (_frame, mut stats): Self::Context) -> Self::Output {
+            stats.predicated += 1;
+            Step
+        }
+    }
+
+    struct Toggle;
+
+    impl Task for Toggle {
+        type Context = (Any<Frame>, Mut<Settings>);
+        type Output = Step;
+
+        fn run(&mut self, (_frame, mut settings): Self::Context) -> Self::Output {
+            settings.enabled = !settings.enabled;
+            Step
+        }
+    }
+
+    struct Summary;
+
+    impl Task for Summary {
+        type Context = (All<Step>, Ref<Stats>);
+        type Output = Stats;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_steps, stats): Self::Context) -> Self::Output {
+            stats.clone()
+        }
+    }
+
+    #[test]
+    fn can_run_tasks_on_conditions() {
+        let headless = Headless::<Stats>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Stats::default());
+            scheduler.add_context(Settings { enabled: true });
+            scheduler.add_task(Summary);
+            scheduler.add_task(EveryThird);
+            scheduler.add_task(Throttled(1.0));
+            // invalid rates are never satisfied
+            scheduler.add_task(Throttled(0.0));
+            scheduler.add_task(Throttled(-1.0));
+            scheduler.add_task(Emit);
+            scheduler.add_task(Listen);
+            scheduler.add_task(WhenEnabled);
+            scheduler.add_task(Toggle);
+        }
+        let mut stats = Stats::default();
+        for _ in 0..6 {
+            stats = headless.step();
+        }
+        assert_eq!(
+            stats,
+            Stats {
+                every: 2,
+                throttled: 1,
+                listened: 3,
+                predicated: 3,
+            }
+        );
+    }
+
+    struct Fragile {
+        policy: FailurePolicy,
+        failing_frames: u64,
+    }
+
+    impl Task for Fragile {
+        type Context = (Any<Frame>,);
+        type Output = Step;
+
+        fn failure_policy(&self) -> FailurePolicy {
+            self.policy
+        }
+
+        fn run(&mut self, (frame,): Self::Context) -> Self::Output {
+            if frame.number <= self.failing_frames {
+                panic!("fragile task has failed");
+            }
+            Step
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct FailureReport {
+        frame: u64,
+        steps: usize,
+        failures: usize,
+    }
+
+    struct FailureReporter;
+
+    impl Task for FailureReporter {
+        type Context = (Any<Frame>, All<Step>, Ref<Errors>);
+        type Output = FailureReport;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn failure_policy(&self) -> FailurePolicy {
+            FailurePolicy::Retry
+        }
+
+        fn run(&mut self, (frame, steps, errors): Self::Context) -> Self::Output {
+            if frame.number == 4 {
+                panic!("reporter has failed");
+            }
+            FailureReport {
+                frame: frame.number,
+                steps: steps.len(),
+                failures: errors.failures().len(),
+            }
+        }
+    }
+
+    #[test]
+    fn can_isolate_panics_of_tasks() {
+        let headless = Headless::<FailureReport>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.add_task(FailureReporter);
+            scheduler.add_task(Increment(1));
+            scheduler.add_task(Fragile {
+                policy: FailurePolicy::Disable,
+                failing_frames: u64::MAX,
+            });
+            scheduler.add_task(Fragile {
+                policy: FailurePolicy::Retry,
+                failing_frames: 2,
+            });
+        }
+        let reports = (0..4).map(|_| headless.step()).collect::<Vec<_>>();
+        assert_eq!(
+            reports,
+            [
+                FailureReport {
+                    frame: 1,
+                    steps: 1,
+                    failures: 2
+                },
+                FailureReport {
+                    frame: 2,
+                    steps: 1,
+                    failures: 3
+                },
+                FailureReport {
+                    frame: 3,
+                    steps: 2,
+                    failures: 3
+                },
+                // loop of the 4th frame is abandoned due to the reporter failure
+                FailureReport {
+                    frame: 5,
+                    steps: 2,
+                    failures: 4
+                },
+            ]
+        );
+
+        let errors = headless
+            .task_manager()
+            .remove_global_context::<Errors>()
+            .expect("Errors to be in the context");
+        let failures = errors.failures();
+        assert_eq!(failures[0].message, "fragile task has failed");
+        assert_eq!(failures[0].cycle, 1);
+        assert_eq!(failures[2].policy, FailurePolicy::Retry);
+        assert_eq!(failures[3].cycle, 4);
+        assert!(failures[3].task.ends_with("FailureReporter"));
+    }
+
+    #[test]
+    fn abandoned_loop_is_reported_to_caller() {
+        let headless = Headless::<FailureReport>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.add_task(FailureReporter);
+            scheduler.add_task(Increment(1));
+        }
+        headless.run_frames(3);
+
+        headless.task_manager().run();
+        let abandoned = headless
+            .task_manager()
+            .wait_for::<FailureReport>()
+            .expect_err("Loop to be abandoned");
+        assert_eq!(abandoned.cycle, 4);
+        assert_eq!(abandoned.failures.len(), 1);
+        assert_eq!(abandoned.failures[0].message, "reporter has failed");
+
+        // the caller decides to start a new loop
+        assert_eq!(headless.step().frame, 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "fragile task has failed")]
+    fn can_abort_on_panic_of_task() {
+        let headless = Headless::<FailureReport>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_task(FailureReporter);
+            scheduler.add_task(Fragile {
+                policy: FailurePolicy::Abort,
+                failing_frames: 1,
+            });
+        }
+        headless.step();
+    }
+
+    struct Trigger;
+
+    struct EmitOnce;
+
+    impl Task for EmitOnce {
+        type Context = (Any<Frame>,);
+        type Output = Trigger;
+
+        fn conditions(&self) -> Vec<Condition> {
+            vec![Condition::every(10)]
+        }
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {
+            Trigger
+        }
+    }
+
+    struct Hold;
+
+    impl Task for Hold {
+        type Context = (Any<Trigger>, Mut<Counter>);
+        type Output = ();
+
+        fn run(&mut self, _: Self::Context) -> Self::Output {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+
+    struct Blocked;
+
+    impl Task for Blocked {
+        type Context = (Any<Trigger>, Mut<Counter>);
+        type Output = ();
+
+        fn run(&mut self, (_trigger, mut counter): Self::Context) -> Self::Output {
+            counter.0 += 1;
+        }
+    }
+
+    struct CountFailures;
+
+    impl Task for CountFailures {
+        type Context = (Any<Frame>, Ref<Errors>);
+        type Output = Done;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn after(&self) -> Vec<Order> {
+            vec![Order::task::<EmitOnce>()]
+        }
+
+        fn run(&mut self, (_frame, errors): Self::Context) -> Self::Output {
+            Done(errors.failures().len() as u32)
+        }
+    }
+
+    #[test]
+    fn tasks_blocked_until_end_of_loop_are_reset() {
+        let headless = Headless::<Done>::new(1);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.add_task(CountFailures);
+            scheduler.add_task(EmitOnce);
+            scheduler.add_task(Hold);
+            scheduler.add_task(Blocked);
+        }
+        // loop ends, when `Blocked` is ready, but `Hold` keeps the lock
+        assert_eq!(headless.step().0, 0);
+        // `Trigger`, selected by `Blocked` in the previous loop, is not available anymore
+        assert_eq!(headless.step().0, 0);
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(headless.step().0, 0);
+    }
+
+    struct Parse;
+
+    impl Task for Parse {
+        type Context = (Any<Frame>,);
+        type Output = Result<Step, String>;
+
+        fn run(&mut self, (frame,): Self::Context) -> Self::Output {
+            if frame.number % 2 == 1 {
+                return Err(format!("odd frame {}", frame.number));
+            }
+            Ok(Step)
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct ErrorsReport {
+        steps: usize,
+        errors: Vec<String>,
+    }
+
+    struct CollectErrors;
+
+    impl Task for CollectErrors {
+        type Context = (Any<Frame>, All<Step>, Take<All<TaskError>>);
+        type Output = ErrorsReport;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_frame, steps, mut errors): Self::Context) -> Self::Output {
+            ErrorsReport {
+                steps: steps.len(),
+                errors: errors
+                    .drain()
+                    .map(|error| error.error.to_string())
+                    .collect(),
+            }
+        }
+    }
+
+    #[test]
+    fn can_route_errors_of_fallible_tasks() {
+        let headless = Headless::<ErrorsReport>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_context(Counter::default());
+            scheduler.add_task(CollectErrors);
+            scheduler.add_task(Increment(1));
+            scheduler.add_fallible_task(Parse);
+        }
+        let reports = (0..3).map(|_| headless.step()).collect::<Vec<_>>();
+        assert_eq!(
+            reports,
+            [
+                ErrorsReport {
+                    steps: 1,
+                    errors: vec![String::from("odd frame 1")],
+                },
+                ErrorsReport {
+                    steps: 2,
+                    errors: vec![],
+                },
+                ErrorsReport {
+                    steps: 1,
+                    errors: vec![String::from("odd frame 3")],
+                },
+            ]
+        );
+    }
+}
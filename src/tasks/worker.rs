@@ -1,7 +1,8 @@
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
-use super::{context, scheduler};
+use super::{context, errors, scheduler};
 use crate::log;
 
 pub fn spawn(
@@ -19,9 +20,22 @@ pub fn spawn(
                 let message = rx.lock().unwrap().recv().unwrap();
                 match message {
                     scheduler::Message::Schedule(mut task) => {
+                        let started = Instant::now();
                         let result = task.run(&context_manager);
+                        let execution = scheduler::Execution {
+                            worker: id,
+                            started,
+                            finished: Instant::now(),
+                        };
+                        let message = match result {
+                            Ok(output) => scheduler::Message::Output(task, output, execution),
+                            Err(payload) => {
+                                let message = errors::panic_message(payload.as_ref());
+                                scheduler::Message::Failure(task, message, execution)
+                            }
+                        };
                         let response = tx.lock().expect("Mutex to be locked");
-                        response.send(scheduler::Message::Output(task, result)).ok();
+                        response.send(message).ok();
                     }
                     scheduler::Message::Kill(index) => {
                         log::info!("worker[{id}] goes off by command #{index}");
@@ -11,7 +11,7 @@ pub use input::ReadInput;
 use winit::event::StartCause;
 
 use crate::graphics::{self, Display, DisplaySetup, Extent2D};
-use crate::tasks::TaskManager;
+use crate::tasks::{self, TaskManager};
 use crate::Application;
 
 /// Window resize request context
@@ -257,9 +257,10 @@ impl<T: Application> winit::application::ApplicationHandler for EventLoop<T> {
                     instance.winit_window.pre_present_notify();
                 }
                 // log::debug!("Wait for presenter...");
-                self.task_manager
-                    .wait_for::<graphics::FramePresenter>()
-                    .present();
+                match self.task_manager.wait_for::<graphics::FramePresenter>() {
+                    Ok(presenter) => presenter.present(),
+                    Err(error) => log::warn!("{}", error),
+                }
                 // log::debug!("...presented");
                 self.task_manager.run();
                 // Note: can be used for debug
@@ -309,6 +310,9 @@ impl<T: Application> winit::application::ApplicationHandler for EventLoop<T> {
 
         if self.close_requested {
             //handler.on_close();
+            if !self.task_manager.shutdown(tasks::SHUTDOWN_TIMEOUT) {
+                log::warn!("tasks were not shut down gracefully");
+            }
             event_loop.exit();
         }
     }
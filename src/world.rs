@@ -1,15 +1,35 @@
 mod camera;
+mod commands;
+mod hierarchy;
+mod pool;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+mod spatial;
 mod storage;
 
+use std::cell::UnsafeCell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
 use std::{any::TypeId, collections::HashMap, marker::PhantomData};
 
 use crate::recursive;
 use crate::utils::{Id, Lock, TypeLock};
 pub use camera::{Camera, Lens, View};
+pub use commands::{ApplyCommands, Commands, CommandsApplied};
+pub use hierarchy::{Children, Parent};
+#[cfg(feature = "snapshot")]
+pub use snapshot::{
+    Error as SnapshotError, Record, Registry, RestoreContext, SaveContext, Snapshot,
+    SNAPSHOT_VERSION,
+};
+pub use spatial::{Bounds, Frustum, SpatialIndex, SpatialIndexUpdated, UpdateSpatialIndex};
+/// Storage of entities of the same archetype, exposed for benchmarks
+#[doc(hidden)]
+pub use storage::Container;
 pub use storage::{Entity, IntoEntity};
 
-#[derive(Default, Debug, Eq, PartialEq)]
+#[derive(Default, Debug, Eq, PartialEq, Clone, Copy)]
 struct Index {
     /// storage::Container Index
     container: usize,
@@ -23,6 +43,12 @@ pub struct World {
     content: Vec<storage::Container>,
     /// Index of the container that holds Entity
     index: HashMap<Id<Entity>, Index>,
+    /// Index of the container by signature of its archetype
+    archetypes: HashMap<storage::Signature, usize>,
+    /// Containers matching queries, grouped by requirements of the queries
+    queries: Mutex<HashMap<Vec<Requirement>, QueryCache>>,
+    /// Optional index of entities by their bounds
+    spatial: Option<spatial::SpatialIndex>,
     // /// Spawn counter for Entity ID generation
     // next_id: u64,
     /// Lock for multithread safety
@@ -35,6 +61,9 @@ impl World {
         Self {
             content: Vec::new(),
             index: HashMap::new(),
+            archetypes: HashMap::new(),
+            queries: Mutex::new(HashMap::new()),
+            spatial: None,
             // next_id: 1,
             lock: Arc::new((Mutex::new(TypeLock::new()), Condvar::new())),
         }
@@ -76,14 +105,27 @@ impl World {
     }
 
     fn lock<'w, Q: Query<'w>>(&self) {
-        let locks = Q::locks();
+        self.lock_types(&Q::locks());
+    }
+
+    /// Waits until the types are locked
+    fn lock_types(&self, locks: &[Lock]) {
         let (mutex, cvar) = &*self.lock;
         let mut lock_manager = mutex.lock().unwrap();
-        while !lock_manager.lock(&locks) {
+        while !lock_manager.lock(locks) {
             lock_manager = cvar.wait(lock_manager).unwrap();
         }
     }
 
+    /// Unlocks the types, locked by [`World::lock_types`]
+    #[cfg(feature = "snapshot")]
+    fn unlock_types(&self, locks: &[Lock]) {
+        let (mutex, cvar) = &*self.lock;
+        let mut lock_manager = mutex.lock().expect("Mutex failed to lock");
+        lock_manager.unlock(locks);
+        cvar.notify_all();
+    }
+
     /// Returns iterator over entities defined by Query pattern
     pub fn query<'w, Q>(
         &'w self,
@@ -93,11 +135,9 @@ impl World {
     {
         self.lock::<Q>();
 
-        let iter = self
-            .content
-            .iter()
-            .filter(|container| Q::matches(container))
-            .flat_map(|container| Q::select(container));
+        let containers = self.containers::<Q>();
+        let iter = (0..containers.len())
+            .flat_map(move |index| Q::select(&self.content[containers[index]]));
 
         QueryIter {
             iter,
@@ -107,39 +147,160 @@ impl World {
     }
 
     /// Execute a system for each entity in the world
+    ///
+    /// See [`World::par_execute`] for parallel execution
     pub fn execute<'w, Q, S>(&'w self, system: S)
     where
         Q: Query<'w> + 'w,
         S: Fn(<<Q as Query<'_>>::Iter as Iterator>::Item),
     {
-        // TODO: parallelize
         for item in self.query::<Q>() {
             system(item);
         }
     }
 
+    /// Returns parallel query over entities defined by Query pattern
+    ///
+    /// Containers are split into chunks, which are processed by the calling thread together with
+    /// a persistent pool of threads. The threads compete with workers of the task manager, so the
+    /// query pays off only for large numbers of entities. Smaller queries run on the calling
+    /// thread.
+    pub fn par_query<'w, Q>(&'w self) -> ParQuery<'w, Q>
+    where
+        Q: Query<'w>,
+    {
+        self.lock::<Q>();
+
+        ParQuery {
+            world: self,
+            chunk_size: PAR_CHUNK_SIZE,
+            locks: Q::locks(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Execute a system for each entity in the world in parallel
+    pub fn par_execute<'w, Q, S>(&'w self, system: S)
+    where
+        Q: Query<'w> + 'w,
+        S: Fn(<<Q as Query<'w>>::Iter as Iterator>::Item) + Sync,
+    {
+        self.par_query::<Q>().for_each(system);
+    }
+
     /// Get componets dor specified entity
     pub fn get<'w, Q>(&'w self, id: &Id<Entity>) -> Option<Q>
     where
         Q: Query<'w>,
     {
         self.lock::<Q>();
-        self.index
-            .get(id)
-            .map(|index| Q::pick(&self.content[index.container], index.address))
+        self.index.get(id).and_then(|index| {
+            let container = &self.content[index.container];
+            if Q::matches(container) {
+                Q::pick(container, index.address)
+            } else {
+                None
+            }
+        })
     }
 
     /// Exiles an entity from the world
+    ///
+    /// The entity is detached from its parent, its children become roots, see
+    /// [`World::exile_recursive`]. It is removed from the spatial index as well.
     pub fn exile(&mut self, id: &Id<Entity>) -> Option<Entity> {
-        self.index
-            .remove(id)
-            .map(|index| self.content[index.container].remove(index.address))
+        let index = self.index.remove(id)?;
+        let entity = self.take(index);
+        self.detach(id, &entity);
+        if let Some(spatial) = self.spatial.as_mut() {
+            spatial.remove(id);
+        }
+        Some(entity)
+    }
+
+    /// Inserts component into the entity, keeping its id
+    ///
+    /// Existing component of the same type is replaced, otherwise the entity is moved to the
+    /// container of the new archetype. Returns false, if there is no such entity or the component
+    /// is maintained by the world itself, like [`Id<Entity>`], [`Parent`] and [`Children`].
+    pub fn insert_component<C>(&mut self, id: &Id<Entity>, component: C) -> bool
+    where
+        C: Send + Sync + 'static,
+    {
+        self.insert_with_targets(id, component, &mut HashMap::new())
+    }
+
+    /// Inserts components into several entities, see [`World::insert_component`]
+    ///
+    /// Returns number of entities, that were found
+    pub fn insert_component_batch<C, I>(&mut self, entries: I) -> usize
+    where
+        C: Send + Sync + 'static,
+        I: IntoIterator<Item = (Id<Entity>, C)>,
+    {
+        let mut targets = HashMap::new();
+        let mut found = 0;
+        for (id, component) in entries.into_iter() {
+            if self.insert_with_targets(&id, component, &mut targets) {
+                found += 1;
+            }
+        }
+        found
+    }
+
+    /// Removes component from the entity, keeping its id
+    ///
+    /// The entity is moved to the container of the new archetype. [`Id<Entity>`] can not be
+    /// removed, as well as [`Parent`] and [`Children`] maintained by [`World::remove_parent`].
+    pub fn remove_component<C>(&mut self, id: &Id<Entity>) -> Option<C>
+    where
+        C: Send + Sync + 'static,
+    {
+        if !Self::removable::<C>() {
+            return None;
+        }
+        self.remove_with_targets(id, &mut HashMap::new())
+    }
+
+    /// Removes components from several entities, see [`World::remove_component`]
+    ///
+    /// Returns removed components with ids of their entities
+    pub fn remove_component_batch<'a, C, I>(&mut self, ids: I) -> Vec<(Id<Entity>, C)>
+    where
+        C: Send + Sync + 'static,
+        I: IntoIterator<Item = &'a Id<Entity>>,
+    {
+        if !Self::removable::<C>() {
+            return Vec::new();
+        }
+        let mut targets = HashMap::new();
+        ids.into_iter()
+            .filter_map(|id| {
+                self.remove_with_targets(id, &mut targets)
+                    .map(|component| (*id, component))
+            })
+            .collect()
+    }
+
+    /// Resets change trackers used by [`Added`] and [`Changed`] filters
+    ///
+    /// Called by the scheduler at the beginning of every loop for the world added with
+    /// [`crate::tasks::Scheduler::add_world`]
+    pub fn clear_trackers(&mut self) {
+        for container in self.content.iter_mut() {
+            container.clear_trackers();
+        }
     }
 
     /// Clear all entities from the world
     pub fn clear(&mut self) {
         self.content.clear();
         self.index.clear();
+        self.archetypes.clear();
+        self.queries.get_mut().expect("Mutex to be locked").clear();
+        if let Some(spatial) = self.spatial.as_mut() {
+            spatial.clear();
+        }
     }
 
     /// Clear entities from the world and reset to initial state
@@ -148,20 +309,189 @@ impl World {
         // self.next_id = 0;
     }
 
+    /// Spawns an entity with reserved id, returns false if the id is taken
+    fn spawn_with_id(&mut self, id: Id<Entity>, entity: Entity) -> bool {
+        if self.index.contains_key(&id) {
+            return false;
+        }
+        let entity = entity.with(id);
+        let container = self
+            .find_container_for_entity(&entity)
+            .unwrap_or_else(|| self.create_container_for_entity(&entity));
+        let address = self.content[container].store(entity);
+        self.index.insert(id, Index { container, address });
+        true
+    }
+
+    /// Inserts component, caching containers of migrated entities by their source containers
+    fn insert_with_targets<C>(
+        &mut self,
+        id: &Id<Entity>,
+        component: C,
+        targets: &mut HashMap<usize, usize>,
+    ) -> bool
+    where
+        C: Send + Sync + 'static,
+    {
+        Self::removable::<C>() && self.store_with_targets(id, component, targets)
+    }
+
+    /// Inserts any component, including ones maintained by the world itself
+    fn store_with_targets<C>(
+        &mut self,
+        id: &Id<Entity>,
+        component: C,
+        targets: &mut HashMap<usize, usize>,
+    ) -> bool
+    where
+        C: Send + Sync + 'static,
+    {
+        let Some(index) = self.index.get(id).copied() else {
+            return false;
+        };
+        let type_id = TypeId::of::<C>();
+        let container = &mut self.content[index.container];
+        if container.contains(type_id) {
+            container.replace(index.address, type_id, Box::new(component));
+        } else {
+            self.migrate(
+                id,
+                index,
+                |entity| entity.set_raw(type_id, Box::new(component)),
+                targets,
+            );
+        }
+        true
+    }
+
+    /// Removes component, caching containers of migrated entities by their source containers
+    fn remove_with_targets<C>(
+        &mut self,
+        id: &Id<Entity>,
+        targets: &mut HashMap<usize, usize>,
+    ) -> Option<C>
+    where
+        C: Send + Sync + 'static,
+    {
+        let index = self.index.get(id).copied()?;
+        let type_id = TypeId::of::<C>();
+        if !self.content[index.container].contains(type_id) {
+            return None;
+        }
+        let mut removed = None;
+        self.migrate(id, index, |entity| removed = entity.remove::<C>(), targets);
+        self.reindex::<C>(id);
+        removed
+    }
+
+    /// Removes the entity from its container
+    ///
+    /// The last entity of the container is moved into the freed row, so its index is fixed
+    fn take(&mut self, index: Index) -> Entity {
+        let container = &mut self.content[index.container];
+        let entity = container.remove(index.address);
+        let moved = container
+            .column::<Id<Entity>>()
+            .and_then(|ids| ids.get(index.address))
+            .map(|id| unsafe { *id.get() });
+        if let Some(moved) = moved {
+            self.index.insert(moved, index);
+        }
+        entity
+    }
+
+    /// Returns false for components maintained by the world itself
+    fn removable<C: 'static>() -> bool {
+        let type_id = TypeId::of::<C>();
+        type_id != TypeId::of::<Id<Entity>>()
+            && type_id != TypeId::of::<Parent>()
+            && type_id != TypeId::of::<Children>()
+    }
+
+    /// Returns component of the entity
+    ///
+    /// Must be used only by methods borrowing the world mutably, so no query holds the component
+    pub(crate) fn component<C: 'static>(&self, id: &Id<Entity>) -> Option<&C> {
+        let index = self.index.get(id)?;
+        self.content[index.container]
+            .column::<C>()?
+            .get(index.address)
+            .map(|cell| unsafe { &*cell.get() })
+    }
+
+    /// Returns mutable component of the entity and marks it as changed
+    fn component_mut<C: 'static>(&mut self, id: &Id<Entity>) -> Option<&mut C> {
+        let index = self.index.get(id)?;
+        self.content[index.container]
+            .get_mut(index.address, TypeId::of::<C>())
+            .and_then(|component| component.downcast_mut::<C>())
+    }
+
+    /// Moves the entity into the container matching its modified archetype
+    ///
+    /// Change trackers of the kept components are preserved
+    fn migrate<F>(
+        &mut self,
+        id: &Id<Entity>,
+        index: Index,
+        modify: F,
+        targets: &mut HashMap<usize, usize>,
+    ) where
+        F: FnOnce(&mut Entity),
+    {
+        let trackers = self.content[index.container].trackers_of(index.address);
+        let mut entity = self.take(index);
+        modify(&mut entity);
+
+        let container = match targets.get(&index.container) {
+            Some(container) => *container,
+            None => {
+                let container = self
+                    .find_container_for_entity(&entity)
+                    .unwrap_or_else(|| self.create_container_for_entity(&entity));
+                targets.insert(index.container, container);
+                container
+            }
+        };
+        let target = &mut self.content[container];
+        let address = target.store(entity);
+        target.restore_trackers(address, &trackers);
+        self.index.insert(*id, Index { container, address });
+    }
+
     fn find_container_for_entity(&self, entity: &Entity) -> Option<usize> {
-        self.content
-            .iter()
-            .enumerate()
-            .find(|(_, container)| container.matches(&mut entity.archetype()))
-            .map(|(index, _)| index)
+        self.archetypes.get(&entity.signature()).copied()
     }
 
     fn create_container_for_entity(&mut self, entity: &Entity) -> usize {
         let index = self.content.len();
         self.content.push(storage::Container::from(entity));
+        self.archetypes.insert(entity.signature(), index);
         index
     }
 
+    /// Returns indices of containers matching the query
+    ///
+    /// Result is cached and updated only when new containers appear
+    fn containers<'w, Q>(&'w self) -> Arc<Vec<usize>>
+    where
+        Q: Query<'w>,
+    {
+        let mut queries = self.queries.lock().expect("Mutex to be locked");
+        let cache = queries.entry(Q::requirements()).or_default();
+        if cache.checked < self.content.len() {
+            let mut containers = cache.containers.as_ref().clone();
+            // containers are never removed, so only the new ones are checked
+            containers.extend(
+                (cache.checked..self.content.len())
+                    .filter(|&index| Q::matches(&self.content[index])),
+            );
+            cache.checked = self.content.len();
+            cache.containers = Arc::new(containers);
+        }
+        Arc::clone(&cache.containers)
+    }
+
     // fn next_id(&mut self) -> u64 {
     //    let next_id = self.next_id;
     //    self.next_id += 1;
@@ -170,46 +500,130 @@ impl World {
 }
 
 /// Abstraction for queries inoked by [`World::query`]
-pub trait Query<'w> {
-    type Iter: Iterator + 'w;
+pub trait Query<'w>: Sized {
+    type Iter: Iterator<Item = Self> + 'w;
+    /// Borrowed storage of selected components
+    type Fetch: 'w;
     /// Returns vector of locks necesary for the query execution
     fn locks() -> Vec<Lock>;
     /// Selects entities from container
     fn select(container: &'w storage::Container) -> Self::Iter;
+    /// Selects entities from the range of container rows
+    fn select_range(container: &'w storage::Container, range: Range<usize>) -> Self::Iter;
     /// Checks if [`Query`] matches the [`storage::Container`]
     fn matches(container: &'w storage::Container) -> bool;
-    /// Pick specific entity by its index in container
-    fn pick(container: &'w storage::Container, entity_index: usize) -> Self;
+    /// Returns sorted requirements of the [`Query`] to archetypes of containers
+    fn requirements() -> Vec<Requirement>;
+    /// Pick specific entity by its index in container, if it passes filters
+    fn pick(container: &'w storage::Container, entity_index: usize) -> Option<Self>;
+    /// Borrows storage of selected components
+    fn fetch(container: &'w storage::Container) -> Self::Fetch;
+    /// Checks if entity in the row passes filters of the [`Query`]
+    fn filter(fetch: &Self::Fetch, entity_index: usize) -> bool;
+    /// Returns selection for the entity in the row
+    ///
+    /// # Safety
+    /// The row must be occupied and pass the filters
+    unsafe fn get(fetch: &Self::Fetch, entity_index: usize) -> Self;
 }
 
 /// Trait defenition of Selector to control mutability of borrows
-pub trait Selector<'w> {
-    type Iter: Iterator + 'w;
+pub trait Selector<'w>: Sized {
+    /// Borrowed storage of the component
+    type Fetch: 'w;
     type Component: std::any::Any;
 
-    fn borrow(container: &'w storage::Container) -> Self::Iter;
-    fn borrow_one(container: &'w storage::Container, entity_index: usize) -> Self;
-    fn lock() -> Lock;
+    /// Borrows storage of the component from container
+    fn fetch(container: &'w storage::Container) -> Self::Fetch;
+    /// Checks if the container has suitable archetype
+    fn matches(container: &'w storage::Container) -> bool {
+        container.contains(TypeId::of::<Self::Component>())
+    }
+    /// Returns requirement to the archetype, that must be consistent with [`Selector::matches`]
+    fn requirement() -> Requirement {
+        Requirement::With(TypeId::of::<Self::Component>())
+    }
+    /// Checks if entity in the row passes the filter
+    fn filter(fetch: &Self::Fetch, entity_index: usize) -> bool;
+    /// Returns selection for the entity in the row
+    ///
+    /// # Safety
+    /// The row must be occupied and pass the filter
+    unsafe fn get(fetch: &Self::Fetch, entity_index: usize) -> Self;
+    /// Returns lock necessary for the selection, if any
+    fn lock() -> Option<Lock>;
+}
+
+/// Merges locks of the same type, so selectors and filters of a component can be combined
+///
+/// Lock for writing takes precedence over lock for reading
+fn merge_locks(locks: impl Iterator<Item = Lock>) -> Vec<Lock> {
+    let mut merged: Vec<Lock> = Vec::new();
+    for lock in locks {
+        let type_id = match lock {
+            Lock::ReadOnly(type_id) | Lock::ReadWrite(type_id) => type_id,
+        };
+        match merged.iter_mut().find(
+            |merged| matches!(merged, Lock::ReadOnly(id) | Lock::ReadWrite(id) if *id == type_id),
+        ) {
+            Some(merged) => {
+                if let Lock::ReadWrite(_) = lock {
+                    *merged = lock;
+                }
+            }
+            None => merged.push(lock),
+        }
+    }
+    merged
+}
+
+/// Requirement of a [`Selector`] to the archetype of a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Requirement {
+    /// Component must be present
+    With(TypeId),
+    /// Component must be absent
+    Without(TypeId),
+    /// Any archetype matches
+    Any,
+}
+
+/// Cached containers matching a query
+#[derive(Default)]
+struct QueryCache {
+    /// Number of containers, that were checked
+    checked: usize,
+    /// Indices of matching containers
+    containers: Arc<Vec<usize>>,
 }
 
+/// Borrowed components of a container
+type Column<'w, C> = &'w [UnsafeCell<C>];
+
+/// Borrowed change trackers of a container
+type Trackers<'w> = &'w [UnsafeCell<storage::Tracker>];
+
 impl<'w, C> Selector<'w> for &'w C
 where
     C: Send + Sync + 'static,
 {
-    // type Iter = std::slice::Iter<'w, C>;
-    type Iter = storage::Iter<'w, C>;
+    type Fetch = Column<'w, C>;
     type Component = C;
 
-    fn borrow(container: &'w storage::Container) -> Self::Iter {
-        container.iter::<C>()
+    fn fetch(container: &'w storage::Container) -> Self::Fetch {
+        container.column::<C>().unwrap()
     }
 
-    fn borrow_one(container: &'w storage::Container, entity_index: usize) -> Self {
-        container.get::<C>(entity_index).unwrap()
+    fn filter(_fetch: &Self::Fetch, _entity_index: usize) -> bool {
+        true
     }
 
-    fn lock() -> Lock {
-        Lock::ReadOnly(TypeId::of::<C>())
+    unsafe fn get(fetch: &Self::Fetch, entity_index: usize) -> Self {
+        &*fetch[entity_index].get()
+    }
+
+    fn lock() -> Option<Lock> {
+        Some(Lock::ReadOnly(TypeId::of::<C>()))
     }
 }
 
@@ -217,81 +631,277 @@ impl<'w, C> Selector<'w> for &'w mut C
 where
     C: Send + Sync + 'static,
 {
-    type Iter = storage::IterMut<'w, C>;
+    type Fetch = (Column<'w, C>, Trackers<'w>);
     type Component = C;
 
-    fn borrow(container: &'w storage::Container) -> Self::Iter {
-        unsafe { container.iter_mut::<C>() }
+    fn fetch(container: &'w storage::Container) -> Self::Fetch {
+        (
+            container.column::<C>().unwrap(),
+            container.trackers(TypeId::of::<C>()).unwrap(),
+        )
+    }
+
+    fn filter(_fetch: &Self::Fetch, _entity_index: usize) -> bool {
+        true
+    }
+
+    /// Mutable borrow marks the component as changed
+    unsafe fn get((column, trackers): &Self::Fetch, entity_index: usize) -> Self {
+        (*trackers[entity_index].get()).changed = true;
+        &mut *column[entity_index].get()
+    }
+
+    fn lock() -> Option<Lock> {
+        Some(Lock::ReadWrite(TypeId::of::<C>()))
+    }
+}
+
+/// Optional selection of a component, that does not affect matching of entities
+impl<'w, S> Selector<'w> for Option<S>
+where
+    S: Selector<'w>,
+{
+    type Fetch = Option<S::Fetch>;
+    type Component = S::Component;
+
+    fn fetch(container: &'w storage::Container) -> Self::Fetch {
+        if S::matches(container) {
+            Some(S::fetch(container))
+        } else {
+            None
+        }
+    }
+
+    fn matches(_container: &'w storage::Container) -> bool {
+        true
+    }
+
+    fn requirement() -> Requirement {
+        Requirement::Any
+    }
+
+    fn filter(_fetch: &Self::Fetch, _entity_index: usize) -> bool {
+        true
     }
 
-    fn borrow_one(container: &'w storage::Container, entity_index: usize) -> Self {
-        unsafe { container.get_mut::<C>(entity_index).unwrap() }
+    unsafe fn get(fetch: &Self::Fetch, entity_index: usize) -> Self {
+        fetch
+            .as_ref()
+            .filter(|fetch| S::filter(fetch, entity_index))
+            .map(|fetch| S::get(fetch, entity_index))
     }
 
-    fn lock() -> Lock {
-        Lock::ReadWrite(TypeId::of::<C>())
+    fn lock() -> Option<Lock> {
+        S::lock()
     }
 }
 
-/// Iterator converting a tuple of iterators into the Iterator of a tuple
-pub struct Zipper<'w, T> {
-    tuple: T,
-    _lifetime: PhantomData<&'w ()>,
+/// Filter of entities having the component
+pub struct With<C>(PhantomData<C>);
+
+/// Filter of entities not having the component
+pub struct Without<C>(PhantomData<C>);
+
+/// Filter of entities with the component added since the last [`World::clear_trackers`] call
+pub struct Added<C>(PhantomData<C>);
+
+/// Filter of entities with the component added or mutably borrowed since the last
+/// [`World::clear_trackers`] call
+pub struct Changed<C>(PhantomData<C>);
+
+impl<'w, C> Selector<'w> for With<C>
+where
+    C: Send + Sync + 'static,
+{
+    type Fetch = ();
+    type Component = C;
+
+    fn fetch(_container: &'w storage::Container) -> Self::Fetch {}
+
+    fn filter(_fetch: &Self::Fetch, _entity_index: usize) -> bool {
+        true
+    }
+
+    unsafe fn get(_fetch: &Self::Fetch, _entity_index: usize) -> Self {
+        With(PhantomData)
+    }
+
+    fn lock() -> Option<Lock> {
+        None
+    }
+}
+
+impl<'w, C> Selector<'w> for Without<C>
+where
+    C: Send + Sync + 'static,
+{
+    type Fetch = ();
+    type Component = C;
+
+    fn fetch(_container: &'w storage::Container) -> Self::Fetch {}
+
+    fn matches(container: &'w storage::Container) -> bool {
+        !container.contains(TypeId::of::<C>())
+    }
+
+    fn requirement() -> Requirement {
+        Requirement::Without(TypeId::of::<C>())
+    }
+
+    fn filter(_fetch: &Self::Fetch, _entity_index: usize) -> bool {
+        true
+    }
+
+    unsafe fn get(_fetch: &Self::Fetch, _entity_index: usize) -> Self {
+        Without(PhantomData)
+    }
+
+    fn lock() -> Option<Lock> {
+        None
+    }
+}
+
+impl<'w, C> Selector<'w> for Added<C>
+where
+    C: Send + Sync + 'static,
+{
+    type Fetch = Trackers<'w>;
+    type Component = C;
+
+    fn fetch(container: &'w storage::Container) -> Self::Fetch {
+        container.trackers(TypeId::of::<C>()).unwrap()
+    }
+
+    fn filter(fetch: &Self::Fetch, entity_index: usize) -> bool {
+        unsafe { (*fetch[entity_index].get()).added }
+    }
+
+    unsafe fn get(_fetch: &Self::Fetch, _entity_index: usize) -> Self {
+        Added(PhantomData)
+    }
+
+    fn lock() -> Option<Lock> {
+        Some(Lock::ReadOnly(TypeId::of::<C>()))
+    }
+}
+
+impl<'w, C> Selector<'w> for Changed<C>
+where
+    C: Send + Sync + 'static,
+{
+    type Fetch = Trackers<'w>;
+    type Component = C;
+
+    fn fetch(container: &'w storage::Container) -> Self::Fetch {
+        container.trackers(TypeId::of::<C>()).unwrap()
+    }
+
+    fn filter(fetch: &Self::Fetch, entity_index: usize) -> bool {
+        unsafe { (*fetch[entity_index].get()).changed }
+    }
+
+    unsafe fn get(_fetch: &Self::Fetch, _entity_index: usize) -> Self {
+        Changed(PhantomData)
+    }
+
+    fn lock() -> Option<Lock> {
+        Some(Lock::ReadOnly(TypeId::of::<C>()))
+    }
+}
+
+/// Iterator over container rows, yielding selections of entities passing the query filters
+pub struct Zipper<'w, Q: Query<'w>> {
+    range: Range<usize>,
+    fetch: Q::Fetch,
+}
+
+impl<'w, Q> Iterator for Zipper<'w, Q>
+where
+    Q: Query<'w>,
+{
+    type Item = Q;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entity_index in self.range.by_ref() {
+            if Q::filter(&self.fetch, entity_index) {
+                return Some(unsafe { Q::get(&self.fetch, entity_index) });
+            }
+        }
+        None
+    }
 }
 
 macro_rules! impl_queries {
     ($($i: ident),*) => {
+        #[allow(non_snake_case)]
         impl<'w, $($i),*> Query<'w> for ($($i,)*)
         where
             $($i: Selector<'w> + 'w,)*
         {
-            type Iter = Zipper<'w, ($($i::Iter,)*)>;
-
-            fn pick(container: &'w storage::Container, entity_index: usize) -> ($($i,)*) {
-                ($({$i::borrow_one(container, entity_index)},)*)
+            type Iter = Zipper<'w, Self>;
+            type Fetch = ($($i::Fetch,)*);
+
+            fn pick(container: &'w storage::Container, entity_index: usize) -> Option<Self> {
+                let fetch = Self::fetch(container);
+                if entity_index < container.len() && Self::filter(&fetch, entity_index) {
+                    Some(unsafe { Self::get(&fetch, entity_index) })
+                } else {
+                    None
+                }
             }
 
             fn select(container: &'w storage::Container) -> Self::Iter {
+                Self::select_range(container, 0..container.len())
+            }
+
+            fn select_range(container: &'w storage::Container, range: Range<usize>) -> Self::Iter {
                 Zipper {
-                    tuple: ($({$i::borrow(container)},)*),
-                    // tuple: ($(container.get::<$i::Component>().unwrap().into_iter(),)*),
-                    _lifetime: PhantomData,
+                    range,
+                    fetch: Self::fetch(container),
                 }
             }
 
             fn matches(container: &'w storage::Container) -> bool
             {
                 $(
-                    container.contains(TypeId::of::<$i::Component>())
+                    $i::matches(container)
                 )&&*
             }
 
+            fn requirements() -> Vec<Requirement> {
+                let mut requirements = vec![$($i::requirement(),)*];
+                requirements.retain(|requirement| *requirement != Requirement::Any);
+                requirements.sort_unstable();
+                requirements.dedup();
+                requirements
+            }
+
             fn locks() -> Vec<Lock> {
-                vec![
-                    $(
-                        $i::lock(),
-                    )*
-                ]
+                merge_locks(
+                    [
+                        $(
+                            $i::lock(),
+                        )*
+                    ]
+                    .into_iter()
+                    .flatten()
+                )
             }
-        }
 
-        #[allow(non_snake_case)]
-        impl<'w, $($i),*> Iterator for Zipper<'w, ($($i,)*)>
-        where
-            $($i: Iterator + 'w,)*
-        {
-            type Item = ($($i::Item,)*);
+            fn fetch(container: &'w storage::Container) -> Self::Fetch {
+                ($($i::fetch(container),)*)
+            }
 
-            fn next(&mut self) -> Option<Self::Item> {
-                let ($(ref mut $i,)*) = self.tuple;
+            fn filter(fetch: &Self::Fetch, entity_index: usize) -> bool {
+                let ($($i,)*) = fetch;
                 $(
-                    let $i = match $i.next() {
-                        None => return None,
-                        Some(item) => item,
-                    };
+                    $i::filter($i, entity_index)
+                )&&*
+            }
 
-                )*
-                Some(($($i,)*))
+            unsafe fn get(fetch: &Self::Fetch, entity_index: usize) -> Self {
+                let ($($i,)*) = fetch;
+                ($($i::get($i, entity_index),)*)
             }
         }
     }
@@ -322,6 +932,78 @@ where
     }
 }
 
+/// Default number of container rows in a chunk of [`ParQuery`]
+const PAR_CHUNK_SIZE: usize = 256;
+
+/// Minimal number of entities per thread of [`ParQuery`]
+const PAR_MIN_ENTITIES: usize = 1024;
+
+/// Parallel Query result
+pub struct ParQuery<'w, Q> {
+    world: &'w World,
+    chunk_size: usize,
+    locks: Vec<Lock>,
+    _phantom: PhantomData<Q>,
+}
+
+impl<'w, Q> ParQuery<'w, Q>
+where
+    Q: Query<'w>,
+{
+    /// Sets number of container rows in a chunk
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Calls the closure for each entity, splitting the work across threads
+    pub fn for_each<S>(self, system: S)
+    where
+        S: Fn(<Q::Iter as Iterator>::Item) + Sync,
+    {
+        let world = self.world;
+        let chunks = world
+            .containers::<Q>()
+            .iter()
+            .map(|&index| (index, &world.content[index]))
+            .flat_map(|(index, container)| {
+                (0..container.len())
+                    .step_by(self.chunk_size)
+                    .map(move |start| {
+                        (index, start..(start + self.chunk_size).min(container.len()))
+                    })
+            })
+            .collect::<Vec<_>>();
+        let entities = chunks.iter().map(|(_, range)| range.len()).sum::<usize>();
+        // each thread gets at least the minimal number of entities
+        let threads = pool::threads()
+            .min(chunks.len())
+            .min(entities / PAR_MIN_ENTITIES)
+            .max(1);
+
+        let next = AtomicUsize::new(0);
+        pool::run(threads, &|| {
+            while let Some((index, range)) = chunks.get(next.fetch_add(1, Ordering::Relaxed)) {
+                // chunks don't overlap, so mutable borrows are exclusive
+                for item in Q::select_range(&world.content[*index], range.clone()) {
+                    system(item);
+                }
+            }
+        });
+    }
+}
+
+impl<'w, Q> Drop for ParQuery<'w, Q> {
+    fn drop(&mut self) {
+        let (mutex, cvar) = &*self.world.lock;
+
+        let mut lock_manager = mutex.lock().expect("Mutex failed to lock");
+
+        lock_manager.unlock(self.locks.as_slice());
+        cvar.notify_all();
+    }
+}
+
 impl<I> Iterator for QueryIter<I>
 where
     I: Iterator,
@@ -404,9 +1086,12 @@ unsafe impl Sync for World {}
 
 #[cfg(test)]
 mod tests {
+    use crate::graphics::Frame;
+    use crate::headless::Headless;
+    use crate::tasks::{Any, Mut, OutputChannel, Task};
     use crate::{Entity, Id};
 
-    use super::World;
+    use super::{Added, Changed, Lock, Query, TypeId, With, Without, World};
 
     #[derive(Debug, Eq, PartialEq, Copy, Clone)]
     struct Armor(u32);
@@ -576,4 +1261,305 @@ mod tests {
         println!("Zombie entities: {zombie_entities:?}");
         assert_eq!(zombie_entities.len(), 0);
     }
+
+    #[test]
+    fn can_execute_in_parallel() {
+        let mut world = spawn();
+        let bulk = (0..2000).map(|i| (SpeedComponent(i), WeightComponent(1)));
+        world.spawn(bulk).count();
+        world.spawn((0..10).map(|i| (SpeedComponent(i),))).count();
+
+        world.par_execute::<(&mut SpeedComponent, &WeightComponent), _>(|(speed, weight)| {
+            speed.0 += weight.0;
+        });
+
+        let visited = std::sync::atomic::AtomicU32::new(0);
+        let total = std::sync::atomic::AtomicU32::new(0);
+        world
+            .par_query::<(&SpeedComponent,)>()
+            .chunk_size(7)
+            .for_each(|(speed,)| {
+                visited.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                total.fetch_add(speed.0, std::sync::atomic::Ordering::Relaxed);
+            });
+
+        // 2 + 9 + 2000 + 10 entities have speed
+        assert_eq!(visited.into_inner(), 2021);
+        let expected = (10 + 50)
+            + 9 * (35 + 5000)
+            + (0..2000).map(|i| i + 1).sum::<u32>()
+            + (0..10).sum::<u32>();
+        assert_eq!(total.into_inner(), expected);
+
+        // locks are released
+        assert_eq!(world.query::<(&mut SpeedComponent,)>().count(), 2021);
+    }
+
+    #[test]
+    fn parallel_queries_run_on_persistent_threads() {
+        let mut world = World::new();
+        world
+            .spawn((0..4096).map(|i| (SpeedComponent(i), WeightComponent(1))))
+            .count();
+
+        let caller = std::thread::current().id();
+        let threads = std::sync::Mutex::new(std::collections::HashSet::new());
+        for _ in 0..3 {
+            world.par_execute::<(&SpeedComponent,), _>(|_| {
+                let thread = std::thread::current();
+                if thread.id() != caller {
+                    let name = thread.name().unwrap_or_default().to_string();
+                    assert!(name.starts_with("dotrix::par["));
+                    threads.lock().unwrap().insert(thread.id());
+                }
+            });
+        }
+        assert!(
+            threads.into_inner().unwrap().len()
+                < std::thread::available_parallelism().unwrap().get()
+        );
+
+        // panic of the system is propagated to the caller, locks are released
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            world.par_execute::<(&mut SpeedComponent,), _>(|(speed,)| {
+                if speed.0 % 1000 == 999 {
+                    panic!("system has failed");
+                }
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(world.query::<(&mut SpeedComponent,)>().count(), 4096);
+    }
+
+    #[test]
+    fn can_query_with_filters() {
+        let mut world = spawn();
+
+        let mut speeds = world
+            .query::<(&SpeedComponent, Without<WeightComponent>)>()
+            .map(|(speed, _)| speed.0)
+            .collect::<Vec<_>>();
+        speeds.sort_unstable();
+        assert_eq!(speeds, [10, 50]);
+
+        assert_eq!(
+            world
+                .query::<(&SpeedComponent, With<WeightComponent>)>()
+                .count(),
+            9
+        );
+
+        let mut damage = world
+            .query::<(&DamageComponent, Option<&Armor>)>()
+            .map(|(damage, armor)| (damage.0, armor.map(|armor| armor.0)))
+            .collect::<Vec<_>>();
+        damage.sort_unstable();
+        assert_eq!(damage, [(45, None), (300, Some(100)), (600, Some(10))]);
+
+        // everything is added and changed before the first reset
+        assert_eq!(world.query::<(Added<SpeedComponent>,)>().count(), 11);
+        world.clear_trackers();
+        assert_eq!(world.query::<(Added<SpeedComponent>,)>().count(), 0);
+        assert_eq!(world.query::<(Changed<SpeedComponent>,)>().count(), 0);
+
+        for (speed, _) in world.query::<(&mut SpeedComponent, With<HealthComponent>)>() {
+            speed.0 += 1;
+        }
+        world.spawn(Some((SpeedComponent(1),))).count();
+
+        let mut changed = world
+            .query::<(&SpeedComponent, Changed<SpeedComponent>)>()
+            .map(|(speed, _)| speed.0)
+            .collect::<Vec<_>>();
+        changed.sort_unstable();
+        assert_eq!(changed, [1, 11]);
+        assert_eq!(world.query::<(Added<SpeedComponent>,)>().count(), 1);
+
+        // mutable selector and filters of the same component share the lock
+        for (speed, _, _) in world.query::<(
+            &mut SpeedComponent,
+            Changed<SpeedComponent>,
+            Added<SpeedComponent>,
+        )>() {
+            speed.0 += 1;
+        }
+        assert_eq!(
+            <(&mut SpeedComponent, Changed<SpeedComponent>) as Query>::locks(),
+            [Lock::ReadWrite(TypeId::of::<SpeedComponent>())]
+        );
+        assert_eq!(world.query::<(&mut SpeedComponent,)>().count(), 12);
+
+        let id = world
+            .query::<(&Id<Entity>, With<Armor>, Without<HealthComponent>)>()
+            .map(|(id, _, _)| *id)
+            .next()
+            .expect("Entity to be found");
+        assert!(world
+            .get::<(&Armor, Option<&SpeedComponent>)>(&id)
+            .is_some());
+        assert!(world.get::<(&Armor, With<HealthComponent>)>(&id).is_none());
+    }
+
+    #[test]
+    fn can_insert_and_remove_components() {
+        let mut world = spawn();
+        let ids = world
+            .query::<(&Id<Entity>, &WeightComponent)>()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let id = ids[0];
+        world.clear_trackers();
+
+        assert!(world.insert_component(&id, Armor(5)));
+        assert!(world.insert_component(&id, SpeedComponent(1)));
+        assert!(!world.insert_component(&Id::null(), Armor(5)));
+        let (speed, armor, weight) = world
+            .get::<(&SpeedComponent, &Armor, &WeightComponent)>(&id)
+            .expect("Entity to keep its id");
+        assert_eq!((speed.0, armor.0, weight.0), (1, 5, 5000));
+        assert_eq!(world.query::<(Added<Armor>,)>().count(), 1);
+        assert_eq!(world.query::<(Added<WeightComponent>,)>().count(), 0);
+        assert_eq!(world.query::<(Changed<SpeedComponent>,)>().count(), 1);
+
+        assert_eq!(world.remove_component::<Armor>(&id), Some(Armor(5)));
+        assert_eq!(world.remove_component::<Armor>(&id), None);
+        assert_eq!(world.remove_component::<Id<Entity>>(&id), None);
+        assert!(!world.insert_component(&id, ids[1]));
+        assert_eq!(
+            world.get::<(&Id<Entity>,)>(&id).map(|(entity,)| *entity),
+            Some(id)
+        );
+        assert_eq!(
+            world.query::<(&WeightComponent, Without<Armor>)>().count(),
+            9
+        );
+
+        let inserted = world.insert_component_batch(ids.iter().map(|id| (*id, HealthComponent(7))));
+        assert_eq!(inserted, 9);
+        assert_eq!(
+            world
+                .query::<(&HealthComponent, &WeightComponent)>()
+                .filter(|(health, _)| health.0 == 7)
+                .count(),
+            9
+        );
+
+        let removed = world.remove_component_batch::<WeightComponent, _>(&ids);
+        assert_eq!(removed.len(), 9);
+        assert!(removed.iter().all(|(_, weight)| weight.0 == 5000));
+        assert_eq!(world.query::<(&WeightComponent,)>().count(), 0);
+        for id in ids.iter() {
+            assert_eq!(
+                world
+                    .get::<(&HealthComponent,)>(id)
+                    .map(|(health,)| health.0),
+                Some(7)
+            );
+        }
+    }
+
+    #[test]
+    fn ids_are_kept_after_exile_from_the_middle() {
+        let mut world = World::new();
+        let ids = world
+            .spawn((0..8).map(|i| (HealthComponent(i),)))
+            .collect::<Vec<_>>();
+        for id in ids.iter().step_by(3) {
+            assert!(world.exile(id).is_some());
+        }
+        assert!(world.remove_component::<HealthComponent>(&ids[1]).is_some());
+
+        assert_eq!(world.query::<(&HealthComponent,)>().count(), 4);
+        for (i, id) in ids.iter().enumerate() {
+            let health = world
+                .get::<(&HealthComponent,)>(id)
+                .map(|(health,)| health.0);
+            let expected = (i % 3 != 0 && i != 1).then_some(i as u32);
+            assert_eq!(health, expected);
+        }
+    }
+
+    #[test]
+    fn can_cache_matching_containers() {
+        let mut world = World::new();
+        world
+            .spawn(Some((HealthComponent(1), SpeedComponent(1))))
+            .count();
+        assert_eq!(
+            world.query::<(&HealthComponent, &SpeedComponent)>().count(),
+            1
+        );
+
+        // the same archetype in a different order of components
+        world
+            .spawn(Some((SpeedComponent(2), HealthComponent(2))))
+            .count();
+        assert_eq!(world.content.len(), 1);
+
+        world.spawn(Some((HealthComponent(3), Armor(3)))).count();
+        assert_eq!(world.content.len(), 2);
+        assert_eq!(world.query::<(&HealthComponent,)>().count(), 3);
+        // queries with the same requirements share the cache entry
+        assert_eq!(
+            world
+                .query::<(&mut HealthComponent, &SpeedComponent)>()
+                .count(),
+            2
+        );
+        assert_eq!(
+            world
+                .query::<(&HealthComponent, Without<Armor>, Option<&DamageComponent>)>()
+                .count(),
+            2
+        );
+        assert_eq!(world.queries.lock().unwrap().len(), 3);
+
+        world.spawn(Some((Armor(4),))).count();
+        assert_eq!(world.query::<(&Armor,)>().count(), 2);
+        assert_eq!(
+            world.query::<(&HealthComponent, Without<Armor>)>().count(),
+            2
+        );
+
+        world.clear();
+        world.spawn(Some((Armor(5), HealthComponent(5)))).count();
+        assert_eq!(world.query::<(&HealthComponent,)>().count(), 1);
+    }
+
+    struct Track(u32);
+
+    impl Task for Track {
+        type Context = (Any<Frame>, Mut<World>);
+        type Output = (usize, usize);
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_frame, mut world): Self::Context) -> Self::Output {
+            self.0 += 1;
+            match self.0 {
+                1 => world.spawn((0..2).map(|i| (HealthComponent(i),))).count(),
+                3 => world.spawn(Some((HealthComponent(2),))).count(),
+                _ => 0,
+            };
+            (
+                world.query::<(Added<HealthComponent>,)>().count(),
+                world.query::<(Changed<HealthComponent>,)>().count(),
+            )
+        }
+    }
+
+    #[test]
+    fn trackers_are_reset_every_loop() {
+        let headless = Headless::<(usize, usize)>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_world(World::new());
+            scheduler.add_task(Track(0));
+        }
+        assert_eq!(headless.step(), (2, 2));
+        assert_eq!(headless.step(), (0, 0));
+        assert_eq!(headless.step(), (1, 1));
+    }
 }
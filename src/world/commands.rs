@@ -0,0 +1,250 @@
+use super::{Entity, IntoEntity, World};
+use crate::log;
+use crate::tasks::{All, Mut, Take, Task};
+use crate::utils::Id;
+
+/// Deferred mutation of the world
+pub type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Buffer of deferred world mutations
+///
+/// Tasks, that have no `Mut<World>` access, return `Commands` as their output. Buffers of all
+/// providers are applied by [`ApplyCommands`] task, so readers of the world are not serialized
+/// with the writers.
+#[derive(Default)]
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    /// Constructs new empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues spawn of an entity, returns id reserved for it
+    pub fn spawn<T>(&mut self, entity: T) -> Id<Entity>
+    where
+        T: IntoEntity + Send + 'static,
+    {
+        let id = Id::new();
+        self.add(move |world| {
+            if !world.spawn_with_id(id, entity.entity()) {
+                log::warn!("Entity {:?} was not spawned: id is taken", id);
+            }
+        });
+        id
+    }
+
+    /// Queues exile of an entity
+    pub fn exile(&mut self, id: Id<Entity>) {
+        self.add(move |world| {
+            world.exile(&id);
+        });
+    }
+
+    /// Queues exile of an entity with all its descendants
+    pub fn exile_recursive(&mut self, id: Id<Entity>) {
+        self.add(move |world| {
+            world.exile_recursive(&id);
+        });
+    }
+
+    /// Queues attachment of an entity to the parent, see [`World::set_parent`]
+    pub fn set_parent(&mut self, id: Id<Entity>, parent: Id<Entity>) {
+        self.add(move |world| {
+            world.set_parent(&id, &parent);
+        });
+    }
+
+    /// Queues detachment of an entity from its parent
+    pub fn remove_parent(&mut self, id: Id<Entity>) {
+        self.add(move |world| {
+            world.remove_parent(&id);
+        });
+    }
+
+    /// Queues insertion of a component, see [`World::insert_component`]
+    pub fn insert_component<C>(&mut self, id: Id<Entity>, component: C)
+    where
+        C: Send + Sync + 'static,
+    {
+        self.add(move |world| {
+            world.insert_component(&id, component);
+        });
+    }
+
+    /// Queues removal of a component, see [`World::remove_component`]
+    pub fn remove_component<C>(&mut self, id: Id<Entity>)
+    where
+        C: Send + Sync + 'static,
+    {
+        self.add(move |world| {
+            world.remove_component::<C>(&id);
+        });
+    }
+
+    /// Queues custom mutation of the world
+    pub fn add<F>(&mut self, command: F)
+    where
+        F: FnOnce(&mut World) + Send + 'static,
+    {
+        self.queue.push(Box::new(command));
+    }
+
+    /// Returns number of queued commands
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if there are no queued commands
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Applies queued commands in order of queueing
+    pub fn apply(self, world: &mut World) {
+        for command in self.queue.into_iter() {
+            command(world);
+        }
+    }
+}
+
+/// Output of [`ApplyCommands`] task
+///
+/// Tasks, that need to see results of the commands, depend on `Any<CommandsApplied>`
+pub struct CommandsApplied;
+
+/// Task applying [`Commands`] of all providers to the world once per loop
+///
+/// `Commands` output must be registered, so buffers of async tasks are kept until applied:
+///
+/// ```no_run
+/// # fn setup(scheduler: &dotrix::tasks::Scheduler) {
+/// scheduler.register::<dotrix::world::Commands>(0);
+/// scheduler.add_task(dotrix::world::ApplyCommands);
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ApplyCommands;
+
+impl Task for ApplyCommands {
+    type Context = (Take<All<Commands>>, Mut<World>);
+    type Output = CommandsApplied;
+
+    fn run(&mut self, (mut buffers, mut world): Self::Context) -> Self::Output {
+        for commands in buffers.drain() {
+            commands.apply(&mut world);
+        }
+        CommandsApplied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApplyCommands, Commands, CommandsApplied};
+    use crate::graphics::Frame;
+    use crate::headless::Headless;
+    use crate::tasks::{Any, OutputChannel, Ref, Task};
+    use crate::world::{With, World};
+    use crate::{Entity, Id};
+
+    struct Bullet(u32);
+
+    struct Expired;
+
+    struct Shoot;
+
+    impl Task for Shoot {
+        type Context = (Any<Frame>, Ref<World>);
+        type Output = Commands;
+
+        fn run(&mut self, (frame, world): Self::Context) -> Self::Output {
+            let mut commands = Commands::new();
+            commands.spawn((Bullet(frame.number as u32),));
+            for (id, bullet) in world.query::<(&Id<Entity>, &Bullet)>() {
+                if bullet.0 + 2 == frame.number as u32 {
+                    commands.insert_component(*id, Expired);
+                }
+            }
+            commands
+        }
+    }
+
+    struct Cleanup;
+
+    impl Task for Cleanup {
+        type Context = (Any<Frame>, Ref<World>);
+        type Output = Commands;
+
+        fn run(&mut self, (_frame, world): Self::Context) -> Self::Output {
+            let mut commands = Commands::new();
+            for (id, _) in world.query::<(&Id<Entity>, With<Expired>)>() {
+                commands.exile(*id);
+            }
+            commands
+        }
+    }
+
+    struct Count;
+
+    impl Task for Count {
+        type Context = (Any<CommandsApplied>, Ref<World>);
+        type Output = Vec<u32>;
+
+        fn output_channel(&self) -> OutputChannel {
+            OutputChannel::Scheduler
+        }
+
+        fn run(&mut self, (_applied, world): Self::Context) -> Self::Output {
+            let mut bullets = world
+                .query::<(&Bullet,)>()
+                .map(|(bullet,)| bullet.0)
+                .collect::<Vec<_>>();
+            bullets.sort();
+            bullets
+        }
+    }
+
+    #[test]
+    fn can_apply_commands_of_readers() {
+        let headless = Headless::<Vec<u32>>::new(2);
+        {
+            let scheduler = headless.scheduler();
+            scheduler.add_world(World::new());
+            scheduler.register::<Commands>(0);
+            scheduler.add_task(ApplyCommands);
+            scheduler.add_task(Shoot);
+            scheduler.add_task(Cleanup);
+            scheduler.add_task(Count);
+        }
+        headless.run_frames(3);
+        // bullets are marked expired two frames after the spawn and exiled in the next frame
+        assert_eq!(headless.step(), [2, 3, 4]);
+        assert_eq!(headless.step(), [3, 4, 5]);
+    }
+
+    #[test]
+    fn commands_are_applied_in_order() {
+        let mut world = World::new();
+        let mut commands = Commands::new();
+        let id = commands.spawn((Bullet(1),));
+        commands.insert_component(id, Expired);
+        commands.remove_component::<Bullet>(id);
+        commands.add(move |world| {
+            world.insert_component(&id, Bullet(2));
+        });
+        assert_eq!(commands.len(), 4);
+        commands.apply(&mut world);
+
+        let (bullet, _) = world
+            .get::<(&Bullet, With<Expired>)>(&id)
+            .expect("Entity to be spawned with reserved id");
+        assert_eq!(bullet.0, 2);
+
+        let mut commands = Commands::new();
+        commands.exile(id);
+        commands.apply(&mut world);
+        assert!(world.get::<(&Bullet,)>(&id).is_none());
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use super::{Entity, World};
+use crate::utils::Id;
+
+/// Parent of an entity
+///
+/// Maintained by the world, see [`World::set_parent`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Parent(Id<Entity>);
+
+impl Parent {
+    /// Returns id of the parent entity
+    pub fn id(&self) -> Id<Entity> {
+        self.0
+    }
+}
+
+/// Children of an entity
+///
+/// Maintained by the world, see [`World::set_parent`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Children(Vec<Id<Entity>>);
+
+impl Children {
+    /// Returns iterator over ids of the children in order of attachment
+    pub fn iter(&self) -> impl Iterator<Item = &Id<Entity>> {
+        self.0.iter()
+    }
+
+    /// Returns number of children
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if there are no children
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns true if the entity is a child
+    pub fn contains(&self, id: &Id<Entity>) -> bool {
+        self.0.contains(id)
+    }
+}
+
+impl World {
+    /// Attaches the entity to the parent, detaching it from the previous one
+    ///
+    /// Returns false, if any of the entities does not exist or the parent is the entity itself
+    /// or its descendant
+    pub fn set_parent(&mut self, id: &Id<Entity>, parent: &Id<Entity>) -> bool {
+        if !self.index.contains_key(id)
+            || !self.index.contains_key(parent)
+            || self.is_ancestor(id, parent)
+        {
+            return false;
+        }
+        self.remove_parent(id);
+        self.store_with_targets(id, Parent(*parent), &mut HashMap::new());
+        match self.component_mut::<Children>(parent) {
+            Some(children) => children.0.push(*id),
+            None => {
+                self.store_with_targets(parent, Children(vec![*id]), &mut HashMap::new());
+            }
+        }
+        true
+    }
+
+    /// Detaches the entity from its parent, returns id of the parent
+    pub fn remove_parent(&mut self, id: &Id<Entity>) -> Option<Id<Entity>> {
+        let parent = self.remove_with_targets::<Parent>(id, &mut HashMap::new())?;
+        self.remove_child(&parent.0, id);
+        Some(parent.0)
+    }
+
+    /// Exiles the entity together with all its descendants
+    ///
+    /// Returns number of exiled entities
+    pub fn exile_recursive(&mut self, id: &Id<Entity>) -> usize {
+        let mut descendants = Vec::new();
+        let mut stack = vec![*id];
+        while let Some(entity) = stack.pop() {
+            if let Some(children) = self.component::<Children>(&entity) {
+                stack.extend(children.iter().copied());
+            }
+            descendants.push(entity);
+        }
+        // leaves are exiled first, so nobody is orphaned
+        descendants
+            .iter()
+            .rev()
+            .filter(|entity| self.exile(entity).is_some())
+            .count()
+    }
+
+    /// Removes links of exiled entity from its parent and children
+    pub(super) fn detach(&mut self, id: &Id<Entity>, entity: &Entity) {
+        if let Some(parent) = entity.get::<Parent>() {
+            self.remove_child(&parent.0, id);
+        }
+        if let Some(children) = entity.get::<Children>() {
+            let mut targets = HashMap::new();
+            for child in children.iter() {
+                self.remove_with_targets::<Parent>(child, &mut targets);
+            }
+        }
+    }
+
+    /// Removes the child from children of the parent
+    fn remove_child(&mut self, parent: &Id<Entity>, id: &Id<Entity>) {
+        let empty = match self.component_mut::<Children>(parent) {
+            Some(children) => {
+                children.0.retain(|child| child != id);
+                children.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            self.remove_with_targets::<Children>(parent, &mut HashMap::new());
+        }
+    }
+
+    /// Returns true if the ancestor is the entity itself or any of its parents
+    fn is_ancestor(&self, ancestor: &Id<Entity>, id: &Id<Entity>) -> bool {
+        let mut current = Some(*id);
+        while let Some(entity) = current {
+            if entity == *ancestor {
+                return true;
+            }
+            current = self.component::<Parent>(&entity).map(Parent::id);
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Children, Parent};
+    use crate::world::{Entity, World};
+    use crate::Id;
+
+    struct Name(&'static str);
+
+    fn spawn(world: &mut World, name: &'static str) -> Id<Entity> {
+        world.spawn(Some((Name(name),))).next().unwrap()
+    }
+
+    #[test]
+    fn can_maintain_hierarchy() {
+        let mut world = World::new();
+        let car = spawn(&mut world, "car");
+        let wheel = spawn(&mut world, "wheel");
+        let bolt = spawn(&mut world, "bolt");
+        let truck = spawn(&mut world, "truck");
+
+        assert!(world.set_parent(&wheel, &car));
+        assert!(world.set_parent(&bolt, &wheel));
+        // cycles are refused
+        assert!(!world.set_parent(&car, &bolt));
+        assert!(!world.set_parent(&car, &car));
+        assert!(!world.set_parent(&car, &Id::null()));
+
+        let (children, name) = world.get::<(&Children, &Name)>(&car).unwrap();
+        assert_eq!(children.iter().copied().collect::<Vec<_>>(), [wheel]);
+        assert_eq!(name.0, "car");
+        let (parent,) = world.get::<(&Parent,)>(&bolt).unwrap();
+        assert_eq!(parent.id(), wheel);
+
+        // reattachment removes the child from the previous parent
+        assert!(world.set_parent(&wheel, &truck));
+        assert!(world.get::<(&Children,)>(&car).is_none());
+        assert!(world
+            .get::<(&Children,)>(&truck)
+            .unwrap()
+            .0
+            .contains(&wheel));
+        assert!(world.remove_component::<Parent>(&wheel).is_none());
+        // hierarchy components can not be inserted directly
+        assert!(!world.insert_component(&car, Children(vec![bolt])));
+        assert_eq!(world.insert_component_batch(Some((car, Parent(truck)))), 0);
+        assert!(world.get::<(&Children,)>(&car).is_none());
+        assert!(world.get::<(&Parent,)>(&car).is_none());
+
+        assert_eq!(world.remove_parent(&wheel), Some(truck));
+        assert_eq!(world.remove_parent(&wheel), None);
+        assert!(world.get::<(&Parent,)>(&wheel).is_none());
+
+        // children of exiled entity become roots
+        assert!(world.set_parent(&wheel, &car));
+        world.exile(&wheel);
+        assert!(world.get::<(&Parent,)>(&bolt).is_none());
+        assert!(world.get::<(&Children,)>(&car).is_none());
+    }
+
+    #[test]
+    fn can_exile_recursively() {
+        let mut world = World::new();
+        let car = spawn(&mut world, "car");
+        let wheels = (0..4)
+            .map(|_| spawn(&mut world, "wheel"))
+            .collect::<Vec<_>>();
+        let bolt = spawn(&mut world, "bolt");
+        let garage = spawn(&mut world, "garage");
+        assert!(world.set_parent(&car, &garage));
+        for wheel in wheels.iter() {
+            assert!(world.set_parent(wheel, &car));
+        }
+        assert!(world.set_parent(&bolt, &wheels[0]));
+
+        assert_eq!(world.exile_recursive(&car), 6);
+        let names = world
+            .query::<(&Name,)>()
+            .map(|(name,)| name.0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["garage"]);
+        assert!(world.get::<(&Children,)>(&garage).is_none());
+    }
+}
@@ -0,0 +1,179 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+
+use crate::log;
+
+/// Persistent threads, executing parallel queries
+static POOL: OnceLock<Pool> = OnceLock::new();
+
+/// Job of a helper thread
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Pool of helper threads, shared by all parallel queries
+struct Pool {
+    tx: Mutex<mpsc::Sender<Job>>,
+    helpers: usize,
+}
+
+impl Pool {
+    /// Constructs pool with a helper thread per available core, except the calling one
+    fn available() -> Self {
+        let threads = thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1);
+        Self::new(threads - 1)
+    }
+
+    fn new(helpers: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let helpers = (1..=helpers)
+            .filter(|id| {
+                let rx = Arc::clone(&rx);
+                thread::Builder::new()
+                    .name(format!("dotrix::par[{}]", id))
+                    .spawn(move || loop {
+                        let job = match rx.lock().expect("Mutex to be locked").recv() {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        };
+                        job();
+                    })
+                    .map_err(|error| log::error!("Could not spawn a thread: {}", error))
+                    .is_ok()
+            })
+            .count();
+        Self {
+            tx: Mutex::new(tx),
+            helpers,
+        }
+    }
+}
+
+/// State of jobs of a single call
+#[derive(Default)]
+struct Scope {
+    /// Caller does not wait for jobs, that are not started yet
+    closed: bool,
+    /// Number of running jobs
+    active: usize,
+    /// One of the jobs has panicked
+    panicked: bool,
+}
+
+/// Returns maximal number of threads, that could run the work at once
+pub fn threads() -> usize {
+    POOL.get_or_init(Pool::available).helpers + 1
+}
+
+/// Runs the work on the calling thread and on up to `threads - 1` helper threads
+///
+/// Returns after all started copies of the work are finished. Helpers, that were busy with other
+/// queries, skip the work, so the work must split itself between the threads.
+pub fn run(threads: usize, work: &(dyn Fn() + Sync)) {
+    POOL.get_or_init(Pool::available).run(threads, work);
+}
+
+impl Pool {
+    fn run(&self, threads: usize, work: &(dyn Fn() + Sync)) {
+        let helpers = threads.saturating_sub(1).min(self.helpers);
+        if helpers == 0 {
+            work();
+            return;
+        }
+
+        let scope = Arc::new((Mutex::new(Scope::default()), Condvar::new()));
+        {
+            let tx = self.tx.lock().expect("Mutex to be locked");
+            for _ in 0..helpers {
+                let scope = Arc::clone(&scope);
+                // SAFETY: the work is called only by started jobs, the caller waits for them
+                let work = unsafe {
+                    std::mem::transmute::<&(dyn Fn() + Sync), &'static (dyn Fn() + Sync)>(work)
+                };
+                let job = Box::new(move || {
+                    let (mutex, cvar) = &*scope;
+                    {
+                        let mut state = mutex.lock().expect("Mutex to be locked");
+                        if state.closed {
+                            return;
+                        }
+                        state.active += 1;
+                    }
+                    let result = panic::catch_unwind(AssertUnwindSafe(work));
+                    let mut state = mutex.lock().expect("Mutex to be locked");
+                    state.active -= 1;
+                    state.panicked |= result.is_err();
+                    cvar.notify_all();
+                });
+                tx.send(job)
+                    .expect("Threads of parallel queries to be running");
+            }
+        }
+
+        // helpers are waited for, even if the work panics on the calling thread
+        let guard = Wait(&scope);
+        work();
+        drop(guard);
+
+        let (mutex, _) = &*scope;
+        if mutex.lock().expect("Mutex to be locked").panicked {
+            panic!("Parallel query has panicked");
+        }
+    }
+}
+
+/// Closes the scope and waits for its running jobs
+struct Wait<'a>(&'a (Mutex<Scope>, Condvar));
+
+impl Drop for Wait<'_> {
+    fn drop(&mut self) {
+        let (mutex, cvar) = self.0;
+        let mut state = mutex.lock().unwrap_or_else(|error| error.into_inner());
+        state.closed = true;
+        while state.active > 0 {
+            state = cvar.wait(state).unwrap_or_else(|error| error.into_inner());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+
+    #[test]
+    fn can_run_work_on_helper_threads() {
+        let pool = Pool::new(3);
+        for _ in 0..3 {
+            // all copies must be started to pass the barrier
+            let barrier = Barrier::new(4);
+            let calls = AtomicUsize::new(0);
+            pool.run(4, &|| {
+                barrier.wait();
+                calls.fetch_add(1, Ordering::Relaxed);
+            });
+            assert_eq!(calls.into_inner(), 4);
+        }
+
+        let caller = std::thread::current().id();
+        let barrier = Barrier::new(2);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            pool.run(2, &|| {
+                barrier.wait();
+                if std::thread::current().id() != caller {
+                    panic!("helper has failed");
+                }
+            });
+        }));
+        assert!(result.is_err());
+
+        // helper threads survive the panic
+        let barrier = Barrier::new(4);
+        pool.run(4, &|| {
+            barrier.wait();
+        });
+    }
+}
@@ -0,0 +1,559 @@
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use super::storage::Component;
+use super::{Entity, Parent, World};
+use crate::loaders::{Asset, Assets};
+use crate::utils::{Id, Lock};
+
+/// Version of the snapshot format, written into every snapshot
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+type SerializeFn = Box<dyn Fn(&dyn Any, &SaveContext) -> Result<Value, Error> + Send + Sync>;
+type DeserializeFn =
+    Box<dyn Fn(Value, &RestoreContext) -> Result<Box<dyn Component>, Error> + Send + Sync>;
+
+struct Entry {
+    name: String,
+    type_id: TypeId,
+    serialize: SerializeFn,
+    deserialize: DeserializeFn,
+}
+
+/// Registry of components, that opted in for snapshots
+///
+/// Components are stored in snapshots under the names, they were registered with, so the names
+/// must stay the same between versions of the game. Unregistered components are not saved.
+#[derive(Default)]
+pub struct Registry {
+    entries: Vec<Entry>,
+    names: HashMap<String, usize>,
+    types: HashMap<TypeId, usize>,
+}
+
+impl Registry {
+    /// Constructs new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers component, that is serialized as is
+    ///
+    /// Returns false, if the name or the component is registered already
+    pub fn register<C>(&mut self, name: &str) -> bool
+    where
+        C: Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        self.register_with::<C>(
+            name,
+            |component, _| Ok(serde_json::to_value(component)?),
+            |value, _| Ok(serde_json::from_value(value)?),
+        )
+    }
+
+    /// Registers component with own serialization functions
+    ///
+    /// Components, that refer to entities or assets, remap their ids using the contexts. Returns
+    /// false, if the name or the component is registered already. Components maintained by the
+    /// world itself, like [`Parent`], are never registered.
+    pub fn register_with<C>(
+        &mut self,
+        name: &str,
+        serialize: fn(&C, &SaveContext) -> Result<Value, Error>,
+        deserialize: fn(Value, &RestoreContext) -> Result<C, Error>,
+    ) -> bool
+    where
+        C: Send + Sync + 'static,
+    {
+        let type_id = TypeId::of::<C>();
+        if self.names.contains_key(name)
+            || self.types.contains_key(&type_id)
+            || !World::removable::<C>()
+        {
+            return false;
+        }
+        let index = self.entries.len();
+        self.entries.push(Entry {
+            name: String::from(name),
+            type_id,
+            serialize: Box::new(move |component, context| {
+                let component = component
+                    .downcast_ref::<C>()
+                    .expect("Component type should match registry entry");
+                serialize(component, context)
+            }),
+            deserialize: Box::new(move |value, context| {
+                deserialize(value, context)
+                    .map(|component| Box::new(component) as Box<dyn Component>)
+            }),
+        });
+        self.names.insert(String::from(name), index);
+        self.types.insert(type_id, index);
+        true
+    }
+
+    /// Returns number of registered components
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no registered components
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Context of the snapshot creation, maps ids to their stable representations
+pub struct SaveContext<'a> {
+    entities: HashMap<Id<Entity>, u64>,
+    assets: &'a Assets,
+}
+
+impl<'a> SaveContext<'a> {
+    /// Returns key of the entity in the snapshot, if the entity is in the world
+    pub fn entity(&self, id: &Id<Entity>) -> Option<u64> {
+        self.entities.get(id).copied()
+    }
+
+    /// Returns name of the asset, if it is loaded
+    pub fn asset<T: Asset>(&self, id: Id<T>) -> Option<&'a str> {
+        self.assets.get(id).map(|asset| asset.name())
+    }
+}
+
+/// Context of the snapshot restoration, maps stable representations to the ids
+pub struct RestoreContext<'a> {
+    entities: HashMap<u64, Id<Entity>>,
+    assets: &'a Assets,
+}
+
+impl<'a> RestoreContext<'a> {
+    /// Returns id of the restored entity by its key in the snapshot
+    pub fn entity(&self, key: u64) -> Result<Id<Entity>, Error> {
+        self.entities
+            .get(&key)
+            .copied()
+            .ok_or(Error::UnknownEntity(key))
+    }
+
+    /// Returns id of the asset by its name, see [`Assets::find`]
+    pub fn asset<T: Asset>(&self, name: &str) -> Result<Id<T>, Error> {
+        self.assets
+            .find(name)
+            .ok_or_else(|| Error::UnknownAsset(String::from(name)))
+    }
+}
+
+/// Entity stored in the snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Key of the entity, unique within the snapshot
+    pub id: u64,
+    /// Key of the parent entity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u64>,
+    /// Components by their registered names
+    pub components: BTreeMap<String, Value>,
+}
+
+/// Saved state of the world, see [`World::snapshot`]
+///
+/// Text representation is meant for editing, binary one is meant for game saves
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Version of the format
+    pub version: u32,
+    /// Saved entities
+    pub entities: Vec<Record>,
+}
+
+impl Snapshot {
+    /// Encodes the snapshot as a pretty printed JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Decodes the snapshot from JSON
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Encodes the snapshot as MessagePack
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        rmp_serde::to_vec_named(self).map_err(|error| Error::Format(error.to_string()))
+    }
+
+    /// Decodes the snapshot from MessagePack
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        rmp_serde::from_slice(bytes).map_err(|error| Error::Format(error.to_string()))
+    }
+}
+
+/// Error of the snapshot creation or restoration
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Error {
+    /// Snapshot has unsupported version of the format
+    Version(u32),
+    /// Snapshot contains component, that is not registered
+    UnknownComponent(String),
+    /// Snapshot refers to an entity, that it does not contain
+    UnknownEntity(u64),
+    /// Snapshot contains several entities with the same key
+    DuplicateEntity(u64),
+    /// Id generated for the entity is already taken in the world
+    EntityTaken(u64),
+    /// Parent of the entity makes a cycle in the hierarchy
+    InvalidParent(u64),
+    /// Snapshot refers to an asset, that is not loaded
+    UnknownAsset(String),
+    /// Snapshot or component could not be encoded or decoded
+    Format(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Version(version) => write!(
+                f,
+                "Snapshot version {} is not supported, expected {}",
+                version, SNAPSHOT_VERSION
+            ),
+            Error::UnknownComponent(name) => write!(f, "Component {} is not registered", name),
+            Error::UnknownEntity(key) => write!(f, "Entity {} is not in the snapshot", key),
+            Error::DuplicateEntity(key) => write!(f, "Entity {} is in the snapshot twice", key),
+            Error::EntityTaken(key) => write!(f, "Id of entity {} is already taken", key),
+            Error::InvalidParent(key) => write!(f, "Parent of entity {} makes a cycle", key),
+            Error::UnknownAsset(name) => write!(f, "Asset {} is not loaded", name),
+            Error::Format(message) => write!(f, "Snapshot format error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Format(error.to_string())
+    }
+}
+
+impl World {
+    /// Saves registered components of all entities
+    ///
+    /// Ids of entities are replaced by keys, unique within the snapshot, and the hierarchy is
+    /// saved as well. Components are locked for reading until the snapshot is done.
+    pub fn snapshot(&self, registry: &Registry, assets: &Assets) -> Result<Snapshot, Error> {
+        let mut locks = vec![
+            Lock::ReadOnly(TypeId::of::<Id<Entity>>()),
+            Lock::ReadOnly(TypeId::of::<Parent>()),
+        ];
+        locks.extend(
+            registry
+                .entries
+                .iter()
+                .map(|entry| Lock::ReadOnly(entry.type_id)),
+        );
+        self.lock_types(&locks);
+        let snapshot = self.save(registry, assets);
+        self.unlock_types(&locks);
+        snapshot
+    }
+
+    /// Spawns entities of the snapshot, returns their ids in order of the snapshot
+    ///
+    /// Entities get new ids, references to them and to assets are remapped. Nothing is spawned,
+    /// if any of the components or the hierarchy could not be restored.
+    pub fn restore(
+        &mut self,
+        snapshot: &Snapshot,
+        registry: &Registry,
+        assets: &Assets,
+    ) -> Result<Vec<Id<Entity>>, Error> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(Error::Version(snapshot.version));
+        }
+        let mut entities = HashMap::with_capacity(snapshot.entities.len());
+        for record in snapshot.entities.iter() {
+            if entities.insert(record.id, self.gen_entity_id()).is_some() {
+                return Err(Error::DuplicateEntity(record.id));
+            }
+        }
+        let context = RestoreContext { entities, assets };
+
+        let mut restored = Vec::with_capacity(snapshot.entities.len());
+        for record in snapshot.entities.iter() {
+            let id = context.entity(record.id)?;
+            let parent = record
+                .parent
+                .map(|parent| context.entity(parent))
+                .transpose()?;
+            let mut entity = Entity::empty();
+            for (name, value) in record.components.iter() {
+                let entry = registry
+                    .names
+                    .get(name)
+                    .map(|&index| &registry.entries[index])
+                    .ok_or_else(|| Error::UnknownComponent(name.clone()))?;
+                entity.set_raw(entry.type_id, (entry.deserialize)(value.clone(), &context)?);
+            }
+            restored.push((record.id, id, parent, entity));
+        }
+
+        let ids = restored.iter().map(|(_, id, _, _)| *id).collect::<Vec<_>>();
+        let mut spawned = Vec::with_capacity(restored.len());
+        let mut hierarchy = Vec::new();
+        for (key, id, parent, entity) in restored.into_iter() {
+            if !self.spawn_with_id(id, entity) {
+                self.exile_restored(&spawned);
+                return Err(Error::EntityTaken(key));
+            }
+            spawned.push(id);
+            if let Some(parent) = parent {
+                hierarchy.push((key, id, parent));
+            }
+        }
+        for (key, id, parent) in hierarchy.iter() {
+            // parents are spawned above, so only a cycle fails
+            if !self.set_parent(id, parent) {
+                self.exile_restored(&spawned);
+                return Err(Error::InvalidParent(*key));
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Exiles entities of the failed restore
+    fn exile_restored(&mut self, ids: &[Id<Entity>]) {
+        for id in ids.iter() {
+            self.exile(id);
+        }
+    }
+
+    fn save(&self, registry: &Registry, assets: &Assets) -> Result<Snapshot, Error> {
+        let context = SaveContext {
+            entities: self
+                .content
+                .iter()
+                .filter_map(|container| container.column::<Id<Entity>>())
+                .flat_map(|ids| ids.iter().map(|id| unsafe { *id.get() }))
+                .zip(0..)
+                .collect(),
+            assets,
+        };
+
+        let mut entities = Vec::with_capacity(context.entities.len());
+        for container in self.content.iter() {
+            let Some(ids) = container.column::<Id<Entity>>() else {
+                continue;
+            };
+            for (row, id) in ids.iter().enumerate() {
+                let id = unsafe { *id.get() };
+                let mut components = BTreeMap::new();
+                for entry in registry.entries.iter() {
+                    if let Some(component) = container.get(row, entry.type_id) {
+                        components
+                            .insert(entry.name.clone(), (entry.serialize)(component, &context)?);
+                    }
+                }
+                let parent = container
+                    .get(row, TypeId::of::<Parent>())
+                    .and_then(|parent| parent.downcast_ref::<Parent>())
+                    .and_then(|parent| context.entity(&parent.id()));
+                entities.push(Record {
+                    id: context.entities[&id],
+                    parent,
+                    components,
+                });
+            }
+        }
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            entities,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Error, Registry, Snapshot, SNAPSHOT_VERSION};
+    use crate::loaders::{Asset, Assets};
+    use crate::world::{Children, Entity, Parent, World};
+    use crate::Id;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Name(String);
+
+    struct Target(Id<Entity>);
+
+    struct Skin(Id<Texture>);
+
+    struct Secret;
+
+    struct Texture(&'static str);
+
+    impl Asset for Texture {
+        fn name(&self) -> &str {
+            self.0
+        }
+    }
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        assert!(registry.register::<Name>("name"));
+        assert!(registry.register_with::<Target>(
+            "target",
+            |target, context| Ok(serde_json::to_value(context.entity(&target.0))?),
+            |value, context| {
+                let key = serde_json::from_value::<u64>(value)?;
+                Ok(Target(context.entity(key)?))
+            },
+        ));
+        assert!(registry.register_with::<Skin>(
+            "skin",
+            |skin, context| Ok(serde_json::to_value(context.asset(skin.0))?),
+            |value, context| {
+                let name = serde_json::from_value::<String>(value)?;
+                Ok(Skin(context.asset(&name)?))
+            },
+        ));
+        registry
+    }
+
+    fn name_of(world: &World, id: &Id<Entity>) -> String {
+        world.component::<Name>(id).unwrap().0.clone()
+    }
+
+    #[test]
+    fn can_snapshot_and_restore_world() {
+        let mut registry = registry();
+        // names, types and components of the world itself are refused
+        assert!(!registry.register::<Name>("title"));
+        assert!(!registry.register_with::<Secret>(
+            "name",
+            |_, _| Ok(serde_json::Value::Null),
+            |_, _| Ok(Secret),
+        ));
+        assert!(!registry.register_with::<Parent>(
+            "parent",
+            |_, _| Ok(serde_json::Value::Null),
+            |_, _| Err(Error::UnknownEntity(0)),
+        ));
+        assert_eq!(registry.len(), 3);
+
+        let mut assets = Assets::new();
+        let skin = assets.set(Texture("camouflage"));
+        let mut world = World::new();
+        let tank = world
+            .spawn(Some((Name(String::from("tank")), Skin(skin), Secret)))
+            .next()
+            .unwrap();
+        let turret = world
+            .spawn(Some((Name(String::from("turret")), Target(tank))))
+            .next()
+            .unwrap();
+        assert!(world.set_parent(&turret, &tank));
+
+        let snapshot = world.snapshot(&registry, &assets).unwrap();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert_eq!(snapshot.entities.len(), 2);
+        let json = snapshot.to_json().unwrap();
+        assert!(json.contains("\"camouflage\""));
+        assert_eq!(Snapshot::from_json(&json).unwrap(), snapshot);
+        let bytes = snapshot.to_bytes().unwrap();
+        assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+
+        // assets are loaded again with new ids and found by their names
+        let mut assets = Assets::new();
+        assets.set(Texture("rust"));
+        let skin = assets.set(Texture("camouflage"));
+        let mut restored = World::new();
+        let ids = restored
+            .restore(&Snapshot::from_bytes(&bytes).unwrap(), &registry, &assets)
+            .unwrap();
+        assert_eq!(ids.len(), 2);
+        assert!(!ids.contains(&tank) && !ids.contains(&turret));
+
+        let tank = *ids
+            .iter()
+            .find(|id| name_of(&restored, id) == "tank")
+            .unwrap();
+        let turret = *ids
+            .iter()
+            .find(|id| name_of(&restored, id) == "turret")
+            .unwrap();
+        assert_eq!(restored.component::<Target>(&turret).unwrap().0, tank);
+        assert_eq!(restored.component::<Parent>(&turret).unwrap().id(), tank);
+        assert!(restored
+            .component::<Children>(&tank)
+            .unwrap()
+            .contains(&turret));
+        assert_eq!(restored.component::<Skin>(&tank).unwrap().0, skin);
+        assert!(restored.component::<Secret>(&tank).is_none());
+    }
+
+    #[test]
+    fn restore_fails_without_side_effects() {
+        let registry = registry();
+        let assets = Assets::new();
+        let mut world = World::new();
+
+        let json = r#"{"version":1,"entities":[
+            {"id":0,"components":{"name":"tank"}},
+            {"id":1,"components":{"name":"turret","target":7}}
+        ]}"#;
+        let snapshot = Snapshot::from_json(json).unwrap();
+        assert_eq!(
+            world.restore(&snapshot, &registry, &assets),
+            Err(Error::UnknownEntity(7))
+        );
+
+        let json = r#"{"version":1,"entities":[
+            {"id":0,"components":{"name":"tank"}},
+            {"id":0,"components":{"name":"turret"}}
+        ]}"#;
+        let snapshot = Snapshot::from_json(json).unwrap();
+        assert_eq!(
+            world.restore(&snapshot, &registry, &assets),
+            Err(Error::DuplicateEntity(0))
+        );
+
+        let json = r#"{"version":1,"entities":[
+            {"id":0,"parent":1,"components":{"name":"tank"}},
+            {"id":1,"parent":0,"components":{"name":"turret"}}
+        ]}"#;
+        let snapshot = Snapshot::from_json(json).unwrap();
+        assert_eq!(
+            world.restore(&snapshot, &registry, &assets),
+            Err(Error::InvalidParent(1))
+        );
+
+        let json = r#"{"version":1,"entities":[{"id":0,"components":{"skin":"rust"}}]}"#;
+        let snapshot = Snapshot::from_json(json).unwrap();
+        assert_eq!(
+            world.restore(&snapshot, &registry, &assets),
+            Err(Error::UnknownAsset(String::from("rust")))
+        );
+
+        let json = r#"{"version":1,"entities":[{"id":0,"components":{"health":100}}]}"#;
+        let snapshot = Snapshot::from_json(json).unwrap();
+        assert_eq!(
+            world.restore(&snapshot, &registry, &assets),
+            Err(Error::UnknownComponent(String::from("health")))
+        );
+
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            entities: Vec::new(),
+        };
+        assert_eq!(
+            world.restore(&snapshot, &registry, &assets),
+            Err(Error::Version(SNAPSHOT_VERSION + 1))
+        );
+        assert_eq!(world.query::<(&Name,)>().count(), 0);
+    }
+}
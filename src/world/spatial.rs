@@ -0,0 +1,581 @@
+use std::collections::HashMap;
+
+use super::{Camera, Changed, Entity, With, World};
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::models::{GlobalTransform, PropagateTransforms, RenderModels, Transform};
+use crate::tasks::{Mut, Order, Task};
+use crate::utils::Id;
+
+/// Default depth of the [`SpatialIndex`] tree
+const DEFAULT_DEPTH: usize = 6;
+
+/// Bounding sphere of an entity in its local space
+///
+/// Entities with bounds are tracked by the [`SpatialIndex`] of the world
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    /// Center of the sphere
+    pub center: Vec3,
+    /// Radius of the sphere
+    pub radius: f32,
+}
+
+impl Bounds {
+    /// Constructs bounding sphere
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Returns the sphere transformed by the matrix
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let scale = matrix
+            .x_axis
+            .truncate()
+            .length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+
+    /// Returns true if the spheres overlap
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.center.distance_squared(other.center)
+            <= (self.radius + other.radius) * (self.radius + other.radius)
+    }
+}
+
+/// Clipping planes of a camera
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes pointing inside
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Constructs frustum from projection and view matrices multiplied together
+    ///
+    /// Depth of the clip space is expected to be in range from 0 to 1
+    pub fn new(proj_view: &Mat4) -> Self {
+        let rows = [
+            proj_view.row(0),
+            proj_view.row(1),
+            proj_view.row(2),
+            proj_view.row(3),
+        ];
+        let planes = [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            rows[2],
+            rows[3] - rows[2],
+        ]
+        .map(|plane| plane / plane.truncate().length());
+        Self { planes }
+    }
+
+    /// Returns true if the sphere is at least partially inside
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(bounds.center) + plane.w >= -bounds.radius)
+    }
+
+    /// Returns true if the box may be at least partially inside
+    fn intersects_box(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // the corner furthest along the normal
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+impl From<&Camera> for Frustum {
+    fn from(camera: &Camera) -> Self {
+        Self::new(&(camera.proj * camera.view))
+    }
+}
+
+struct Node {
+    center: Vec3,
+    half: f32,
+    /// Index of the first of eight children
+    children: Option<usize>,
+    items: Vec<Id<Entity>>,
+}
+
+impl Node {
+    fn new(center: Vec3, half: f32) -> Self {
+        Self {
+            center,
+            half,
+            children: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// Returns true if the sphere is inside the loose bounds of the node, twice larger than the
+    /// node itself
+    fn holds(&self, bounds: &Bounds) -> bool {
+        ((bounds.center - self.center).abs() + bounds.radius).max_element() <= 2.0 * self.half
+    }
+
+    /// Returns corners of the loose bounds
+    fn loose(&self) -> (Vec3, Vec3) {
+        let half = Vec3::splat(2.0 * self.half);
+        (self.center - half, self.center + half)
+    }
+}
+
+struct Entry {
+    node: usize,
+    bounds: Bounds,
+}
+
+/// Loose octree of world-space bounding spheres
+///
+/// Entities are placed into the deepest node, that is not smaller than their spheres, so moving
+/// entities are relocated only when they leave the loose bounds of their nodes. Entities outside
+/// of the tree bounds are kept in the root node.
+pub struct SpatialIndex {
+    nodes: Vec<Node>,
+    entries: HashMap<Id<Entity>, Entry>,
+    depth: usize,
+}
+
+impl SpatialIndex {
+    /// Constructs the index covering a cube with the center and the edge size
+    pub fn new(center: Vec3, size: f32) -> Self {
+        Self {
+            nodes: vec![Node::new(center, size / 2.0)],
+            entries: HashMap::new(),
+            depth: DEFAULT_DEPTH,
+        }
+    }
+
+    /// Sets maximal depth of the tree
+    #[must_use]
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Inserts the entity or updates its world-space bounds
+    pub fn update(&mut self, id: Id<Entity>, bounds: Bounds) {
+        if let Some(entry) = self.entries.get_mut(&id) {
+            if entry.bounds == bounds {
+                return;
+            }
+            entry.bounds = bounds;
+            // entities of the root may fit deeper after the move, so they are always relocated
+            if entry.node != 0 && self.nodes[entry.node].holds(&bounds) {
+                return;
+            }
+            self.remove(&id);
+        }
+        let node = self.locate(&bounds);
+        self.nodes[node].items.push(id);
+        self.entries.insert(id, Entry { node, bounds });
+    }
+
+    /// Removes the entity, returns false if it was not indexed
+    pub fn remove(&mut self, id: &Id<Entity>) -> bool {
+        let Some(entry) = self.entries.remove(id) else {
+            return false;
+        };
+        let items = &mut self.nodes[entry.node].items;
+        if let Some(position) = items.iter().position(|item| item == id) {
+            items.swap_remove(position);
+        }
+        true
+    }
+
+    /// Removes all entities
+    pub fn clear(&mut self) {
+        let root = &self.nodes[0];
+        let root = Node::new(root.center, root.half);
+        self.nodes = vec![root];
+        self.entries.clear();
+    }
+
+    /// Returns world-space bounds of the entity
+    pub fn bounds(&self, id: &Id<Entity>) -> Option<&Bounds> {
+        self.entries.get(id).map(|entry| &entry.bounds)
+    }
+
+    /// Returns number of indexed entities
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no indexed entities
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns entities, which bounds overlap the sphere
+    pub fn within_radius(&self, center: Vec3, radius: f32) -> Vec<Id<Entity>> {
+        let sphere = Bounds::new(center, radius);
+        self.search(
+            |(min, max)| sphere.center.clamp(min, max).distance(sphere.center) <= radius,
+            |bounds| bounds.intersects(&sphere),
+        )
+    }
+
+    /// Returns entities, which bounds are at least partially inside the frustum
+    pub fn in_frustum(&self, frustum: &Frustum) -> Vec<Id<Entity>> {
+        self.search(
+            |(min, max)| frustum.intersects_box(min, max),
+            |bounds| frustum.intersects(bounds),
+        )
+    }
+
+    /// Walks the nodes accepted by the node filter, returns entities accepted by the bounds filter
+    fn search<N, B>(&self, node_filter: N, bounds_filter: B) -> Vec<Id<Entity>>
+    where
+        N: Fn((Vec3, Vec3)) -> bool,
+        B: Fn(&Bounds) -> bool,
+    {
+        let mut result = Vec::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            // root holds entities outside of the tree bounds, so it is always checked
+            if index != 0 && !node_filter(node.loose()) {
+                continue;
+            }
+            result.extend(
+                node.items
+                    .iter()
+                    .filter(|id| bounds_filter(&self.entries[*id].bounds))
+                    .copied(),
+            );
+            if let Some(first) = node.children {
+                stack.extend(first..first + 8);
+            }
+        }
+        result
+    }
+
+    /// Returns the deepest node, that fits the bounds, creating missing nodes
+    fn locate(&mut self, bounds: &Bounds) -> usize {
+        let root = &self.nodes[0];
+        if ((bounds.center - root.center).abs().max_element()) > root.half {
+            return 0;
+        }
+        let mut index = 0;
+        for _ in 0..self.depth {
+            let node = &self.nodes[index];
+            let half = node.half / 2.0;
+            if bounds.radius > half {
+                break;
+            }
+            let octant = bounds.center.cmpge(node.center).bitmask() as usize;
+            let first = match node.children {
+                Some(first) => first,
+                None => {
+                    let center = node.center;
+                    let first = self.nodes.len();
+                    self.nodes.extend((0..8).map(|octant| {
+                        let offset = Vec3::new(
+                            if octant & 1 != 0 { half } else { -half },
+                            if octant & 2 != 0 { half } else { -half },
+                            if octant & 4 != 0 { half } else { -half },
+                        );
+                        Node::new(center + offset, half)
+                    }));
+                    self.nodes[index].children = Some(first);
+                    first
+                }
+            };
+            index = first + octant;
+        }
+        index
+    }
+}
+
+impl World {
+    /// Sets the spatial index and fills it with all entities with [`Bounds`]
+    pub fn set_spatial_index(&mut self, mut index: SpatialIndex) {
+        index.clear();
+        for (id, bounds) in self.world_bounds() {
+            index.update(id, bounds);
+        }
+        self.spatial = Some(index);
+    }
+
+    /// Returns the spatial index, if it was set
+    pub fn spatial_index(&self) -> Option<&SpatialIndex> {
+        self.spatial.as_ref()
+    }
+
+    /// Updates the spatial index from [`Bounds`] and transforms of entities
+    ///
+    /// [`GlobalTransform`] is used, if the entity has one, otherwise the local [`Transform`].
+    /// Only entities with any of them changed since the change trackers were reset are updated,
+    /// see [`crate::tasks::Scheduler::add_world`]. Exiled entities and entities without bounds
+    /// are removed from the index at once.
+    pub fn update_spatial_index(&mut self) {
+        let Some(mut index) = self.spatial.take() else {
+            return;
+        };
+        let mut changed = self
+            .query::<(&Id<Entity>, Changed<Bounds>)>()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        changed.extend(
+            self.query::<(&Id<Entity>, With<Bounds>, Changed<GlobalTransform>)>()
+                .map(|(id, _, _)| *id),
+        );
+        changed.extend(
+            self.query::<(&Id<Entity>, With<Bounds>, Changed<Transform>)>()
+                .map(|(id, _, _)| *id),
+        );
+        for id in changed {
+            if let Some(bounds) = self.entity_bounds(&id) {
+                index.update(id, bounds);
+            }
+        }
+        self.spatial = Some(index);
+    }
+
+    /// Updates the entity in the spatial index after removal of a component, that affects it
+    pub(super) fn reindex<C: 'static>(&mut self, id: &Id<Entity>) {
+        let type_id = std::any::TypeId::of::<C>();
+        if type_id != std::any::TypeId::of::<Bounds>()
+            && type_id != std::any::TypeId::of::<GlobalTransform>()
+            && type_id != std::any::TypeId::of::<Transform>()
+        {
+            return;
+        }
+        let bounds = self.entity_bounds(id);
+        if let Some(index) = self.spatial.as_mut() {
+            match bounds {
+                Some(bounds) => index.update(*id, bounds),
+                None => {
+                    index.remove(id);
+                }
+            }
+        }
+    }
+
+    /// Returns entities, which bounds overlap the sphere
+    ///
+    /// Without the spatial index all entities with [`Bounds`] are checked, otherwise the result
+    /// is as of the last [`World::update_spatial_index`] call
+    pub fn within_radius(&self, center: Vec3, radius: f32) -> Vec<Id<Entity>> {
+        match self.spatial.as_ref() {
+            Some(index) => index.within_radius(center, radius),
+            None => {
+                let sphere = Bounds::new(center, radius);
+                self.world_bounds()
+                    .filter(|(_, bounds)| bounds.intersects(&sphere))
+                    .map(|(id, _)| id)
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns entities, which bounds are at least partially inside the frustum
+    ///
+    /// Without the spatial index all entities with [`Bounds`] are checked, otherwise the result
+    /// is as of the last [`World::update_spatial_index`] call
+    pub fn in_frustum(&self, frustum: &Frustum) -> Vec<Id<Entity>> {
+        match self.spatial.as_ref() {
+            Some(index) => index.in_frustum(frustum),
+            None => self
+                .world_bounds()
+                .filter(|(_, bounds)| frustum.intersects(bounds))
+                .map(|(id, _)| id)
+                .collect(),
+        }
+    }
+
+    /// Returns world-space bounds of all entities with [`Bounds`]
+    fn world_bounds(&self) -> impl Iterator<Item = (Id<Entity>, Bounds)> + '_ {
+        self.query::<(
+            &Id<Entity>,
+            &Bounds,
+            Option<&GlobalTransform>,
+            Option<&Transform>,
+        )>()
+        .map(|(id, bounds, global, transform)| {
+            (*id, bounds.transform(&world_matrix(global, transform)))
+        })
+    }
+
+    /// Returns world-space bounds of the entity, if it has [`Bounds`]
+    fn entity_bounds(&mut self, id: &Id<Entity>) -> Option<Bounds> {
+        let bounds = self.component::<Bounds>(id)?;
+        let matrix = world_matrix(
+            self.component::<GlobalTransform>(id),
+            self.component::<Transform>(id),
+        );
+        Some(bounds.transform(&matrix))
+    }
+}
+
+/// Returns matrix transforming the entity into the world space
+fn world_matrix(global: Option<&GlobalTransform>, transform: Option<&Transform>) -> Mat4 {
+    match (global, transform) {
+        (Some(global), _) => global.matrix,
+        (None, Some(transform)) => transform.model.matrix(),
+        (None, None) => Mat4::IDENTITY,
+    }
+}
+
+/// Output of [`UpdateSpatialIndex`] task
+pub struct SpatialIndexUpdated;
+
+/// Task updating the spatial index of the world once per loop
+///
+/// Tasks, that query the index, depend on `Any<SpatialIndexUpdated>`
+#[derive(Default)]
+pub struct UpdateSpatialIndex;
+
+impl Task for UpdateSpatialIndex {
+    type Context = (Mut<World>,);
+    type Output = SpatialIndexUpdated;
+
+    fn after(&self) -> Vec<Order> {
+        vec![Order::task::<PropagateTransforms>()]
+    }
+
+    fn before(&self) -> Vec<Order> {
+        vec![Order::task::<RenderModels>()]
+    }
+
+    fn run(&mut self, (mut world,): Self::Context) -> Self::Output {
+        world.update_spatial_index();
+        SpatialIndexUpdated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bounds, Frustum, SpatialIndex};
+    use crate::math::{Mat4, Vec3};
+    use crate::models::{Transform, Transform3D};
+    use crate::world::{Camera, Entity, World};
+    use crate::Id;
+
+    fn transform(x: f32, y: f32, z: f32) -> Transform {
+        Transform::new(
+            Transform3D::from_translation(Vec3::new(x, y, z)),
+            Vec::new(),
+        )
+    }
+
+    fn sorted(mut ids: Vec<Id<Entity>>) -> Vec<Id<Entity>> {
+        ids.sort_by_key(|id| *id.uuid());
+        ids
+    }
+
+    #[test]
+    fn index_matches_linear_search() {
+        let mut world = World::new();
+        world.spawn_and_count((0..1000).map(|i| {
+            let x = (i % 10) as f32 * 10.0 - 50.0;
+            let y = (i / 10 % 10) as f32 * 10.0 - 50.0;
+            let z = (i / 100) as f32 * 10.0 - 50.0;
+            // some entities are out of the index bounds
+            let scale = if i % 7 == 0 { 3.0 } else { 1.0 };
+            (
+                Bounds::new(Vec3::ZERO, (i % 5) as f32 * 0.5 + 0.1),
+                transform(x * scale, y, z),
+            )
+        }));
+        let queries = [
+            (Vec3::ZERO, 12.0),
+            (Vec3::new(40.0, -40.0, 10.0), 5.0),
+            (Vec3::new(130.0, 0.0, 0.0), 30.0),
+            (Vec3::new(-20.0, 20.0, 0.0), 0.5),
+        ];
+        let expected = queries
+            .iter()
+            .map(|(center, radius)| sorted(world.within_radius(*center, *radius)))
+            .collect::<Vec<_>>();
+        let camera = Camera::new(
+            Mat4::perspective_rh(1.0, 1.0, 0.1, 60.0),
+            Camera::at(0.0, 0.0, 30.0).target(Vec3::ZERO),
+            Vec3::ZERO,
+        );
+        let frustum = Frustum::from(&camera);
+        let visible = sorted(world.in_frustum(&frustum));
+        assert!(!visible.is_empty() && visible.len() < 1000);
+
+        world.set_spatial_index(SpatialIndex::new(Vec3::ZERO, 128.0));
+        world.update_spatial_index();
+        assert_eq!(world.spatial_index().unwrap().len(), 1000);
+        for ((center, radius), expected) in queries.iter().zip(expected.iter()) {
+            assert!(!expected.is_empty());
+            assert_eq!(&sorted(world.within_radius(*center, *radius)), expected);
+        }
+        assert_eq!(sorted(world.in_frustum(&frustum)), visible);
+    }
+
+    #[test]
+    fn index_follows_changes_of_entities() {
+        let mut world = World::new();
+        world.set_spatial_index(SpatialIndex::new(Vec3::ZERO, 64.0).with_depth(4));
+        let ids = world
+            .spawn((0..3).map(|i| {
+                (
+                    Bounds::new(Vec3::ZERO, 1.0),
+                    transform(i as f32 * 10.0, 0.0, 0.0),
+                )
+            }))
+            .collect::<Vec<_>>();
+        assert!(world.within_radius(Vec3::ZERO, 1.0).is_empty());
+        world.update_spatial_index();
+        assert_eq!(world.within_radius(Vec3::ZERO, 1.0), [ids[0]]);
+        world.clear_trackers();
+
+        // moved entity is found at the new position
+        for (id, transform) in world.query::<(&Id<Entity>, &mut Transform)>() {
+            if *id == ids[0] {
+                transform.model.translate = Vec3::new(-20.0, 0.0, 0.0);
+            }
+        }
+        world.update_spatial_index();
+        assert!(world.within_radius(Vec3::ZERO, 1.0).is_empty());
+        assert_eq!(
+            world.within_radius(Vec3::new(-20.0, 0.0, 0.0), 1.0),
+            [ids[0]]
+        );
+        assert_eq!(
+            world
+                .spatial_index()
+                .unwrap()
+                .bounds(&ids[0])
+                .unwrap()
+                .center,
+            Vec3::new(-20.0, 0.0, 0.0)
+        );
+        world.clear_trackers();
+
+        // exiled entities and entities without bounds are removed at once
+        world.exile(&ids[1]);
+        assert!(world
+            .within_radius(Vec3::new(10.0, 0.0, 0.0), 1.0)
+            .is_empty());
+        world.remove_component::<Bounds>(&ids[2]);
+        assert_eq!(world.spatial_index().unwrap().len(), 1);
+        assert!(world
+            .within_radius(Vec3::new(20.0, 0.0, 0.0), 1.0)
+            .is_empty());
+
+        // added bounds are indexed
+        world.insert_component(&ids[2], Bounds::new(Vec3::ZERO, 2.0));
+        world.update_spatial_index();
+        assert_eq!(
+            world.within_radius(Vec3::new(20.0, 0.0, 0.0), 1.0),
+            [ids[2]]
+        );
+    }
+}
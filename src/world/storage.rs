@@ -1,14 +1,35 @@
 use std::any::{Any, TypeId};
 use std::cell::UnsafeCell;
 use std::collections::{hash_map, HashMap};
-use std::marker::PhantomData;
 
 /// Entity structure has only id field and represent an agregation of components
 pub struct Entity {
-    map: HashMap<TypeId, Box<dyn Any>>,
+    map: HashMap<TypeId, Box<dyn Component>>,
 }
 
-pub type ComponentsList = Vec<Option<UnsafeCell<Box<dyn Any>>>>;
+/// Type-erased component, that knows how to construct a column for its type
+pub trait Component: Any {
+    /// Returns reference to the component as [`Any`]
+    fn as_any(&self) -> &dyn Any;
+    /// Converts boxed component into [`Any`]
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+    /// Constructs empty column for components of the same type
+    fn column(&self) -> Box<dyn Column>;
+}
+
+impl<T: Any> Component for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn column(&self) -> Box<dyn Column> {
+        Box::new(Dense::<T>(Vec::new()))
+    }
+}
 
 impl Entity {
     pub fn new<T: IntoEntity>(tuple: T) -> Self {
@@ -26,14 +47,22 @@ impl Entity {
         self
     }
 
-    pub fn set_raw(&mut self, component_type_id: TypeId, component: Box<dyn Any>) {
+    pub fn set_raw(&mut self, component_type_id: TypeId, component: Box<dyn Component>) {
         self.map.insert(component_type_id, component);
     }
 
+    /// Removes component from the entity
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|component| component.into_any().downcast::<T>().ok())
+            .map(|component| *component)
+    }
+
     pub fn get<T: Any>(&self) -> Option<&T> {
         self.map
             .get(&TypeId::of::<T>())
-            .and_then(|v| v.downcast_ref())
+            .and_then(|v| (**v).as_any().downcast_ref())
     }
 
     pub fn archetype(&self) -> Archetype {
@@ -42,10 +71,27 @@ impl Entity {
             len: self.map.len(),
         }
     }
+
+    /// Returns signature of the entity archetype
+    pub fn signature(&self) -> Signature {
+        Signature::from(self.map.keys().copied().collect::<Vec<_>>())
+    }
+}
+
+/// Sorted set of component types, that identifies an archetype
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Signature(Vec<TypeId>);
+
+impl From<Vec<TypeId>> for Signature {
+    fn from(mut type_ids: Vec<TypeId>) -> Self {
+        type_ids.sort_unstable();
+        type_ids.dedup();
+        Self(type_ids)
+    }
 }
 
 pub struct Archetype<'a> {
-    inner: hash_map::Keys<'a, TypeId, Box<dyn Any>>,
+    inner: hash_map::Keys<'a, TypeId, Box<dyn Component>>,
     len: usize,
 }
 
@@ -65,8 +111,8 @@ impl<'a> Iterator for Archetype<'a> {
 }
 
 impl IntoIterator for Entity {
-    type Item = (TypeId, Box<dyn Any>);
-    type IntoIter = hash_map::IntoIter<TypeId, Box<dyn Any>>;
+    type Item = (TypeId, Box<dyn Component>);
+    type IntoIter = hash_map::IntoIter<TypeId, Box<dyn Component>>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -100,7 +146,7 @@ macro_rules! impl_into_components_map {
             fn entity(self) -> Entity {
                 let ($($i,)*) = self;
                 let map = [
-                    $((TypeId::of::<$i>(), Box::new($i) as Box<dyn Any>),)*
+                    $((TypeId::of::<$i>(), Box::new($i) as Box<dyn Component>),)*
                 ]
                 .into_iter()
                 .collect::<HashMap<_,_>>();
@@ -132,12 +178,94 @@ impl_into_components_map!((A, B, C, D, E, F, G, H, I, J, K, L, M, N));
 impl_into_components_map!((A, B, C, D, E, F, G, H, I, J, K, L, M, N, O));
 impl_into_components_map!((A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P));
 
+/// Change tracker of a component
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tracker {
+    /// Component was added since the last trackers reset
+    pub added: bool,
+    /// Component was added or mutably borrowed since the last trackers reset
+    pub changed: bool,
+}
+
+pub type TrackersList = Vec<UnsafeCell<Tracker>>;
+
+/// Type-erased contiguous storage of components of the same type
+pub trait Column {
+    /// Returns the column as [`Any`] for downcasting to the typed storage
+    fn as_any(&self) -> &dyn Any;
+    /// Appends the component to the end of the column
+    fn push(&mut self, component: Box<dyn Component>);
+    /// Removes the component, replacing it with the last one
+    fn swap_remove(&mut self, row: usize) -> Box<dyn Component>;
+    /// Replaces the component, returning the previous one
+    fn replace(&mut self, row: usize, component: Box<dyn Component>) -> Box<dyn Component>;
+    /// Returns reference to the component
+    fn get(&self, row: usize) -> Option<&dyn Any>;
+    /// Returns mutable reference to the component
+    fn get_mut(&mut self, row: usize) -> Option<&mut dyn Any>;
+}
+
+/// Components of type `T` stored contiguously
+///
+/// Cells allow mutable borrows of different rows through the shared borrow of the column, which
+/// are guarded by the world locks
+pub struct Dense<T>(Vec<UnsafeCell<T>>);
+
+impl<T> Dense<T> {
+    /// Returns components of all rows
+    pub fn as_slice(&self) -> &[UnsafeCell<T>] {
+        self.0.as_slice()
+    }
+}
+
+impl<T: Any> Column for Dense<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn push(&mut self, component: Box<dyn Component>) {
+        let component = component
+            .into_any()
+            .downcast::<T>()
+            .expect("Component type should match column");
+        self.0.push(UnsafeCell::new(*component));
+    }
+
+    fn swap_remove(&mut self, row: usize) -> Box<dyn Component> {
+        Box::new(self.0.swap_remove(row).into_inner())
+    }
+
+    fn replace(&mut self, row: usize, component: Box<dyn Component>) -> Box<dyn Component> {
+        let component = component
+            .into_any()
+            .downcast::<T>()
+            .expect("Component type should match column");
+        Box::new(std::mem::replace(self.0[row].get_mut(), *component))
+    }
+
+    fn get(&self, row: usize) -> Option<&dyn Any> {
+        self.0
+            .get(row)
+            .map(|component| unsafe { &*component.get() } as &dyn Any)
+    }
+
+    fn get_mut(&mut self, row: usize) -> Option<&mut dyn Any> {
+        self.0
+            .get_mut(row)
+            .map(|component| component.get_mut() as &mut dyn Any)
+    }
+}
+
 /// Stores Entities of the same archetype
+///
+/// Components are stored densely: removal of an entity moves the last one into the freed row
 pub struct Container {
     /// TypeId identifies component
-    /// Vec stores components of different entities
-    data: HashMap<TypeId, ComponentsList>,
-    removed: Vec<usize>,
+    /// Column stores components of different entities
+    data: HashMap<TypeId, Box<dyn Column>>,
+    /// Change trackers of components
+    trackers: HashMap<TypeId, TrackersList>,
+    /// Number of stored entities
     len: usize,
 }
 
@@ -151,129 +279,135 @@ impl Container {
             && archetype.len() == self.data.len()
     }
 
+    /// Appends the entity, returns its row
     pub fn store(&mut self, entity: Entity) -> usize {
-        let index = self.removed.pop().unwrap_or_else(|| self.next_index());
+        let tracker = Tracker {
+            added: true,
+            changed: true,
+        };
 
         for (component_type_id, component) in entity.into_iter() {
-            let components_list = self
-                .data
+            self.data
                 .get_mut(&component_type_id)
-                .expect("Entity should match container");
-            let components_list_len = components_list.len();
-            let component_cell = Some(UnsafeCell::new(component));
-
-            if index < components_list_len {
-                components_list[index] = component_cell;
-            } else if index == components_list_len {
-                components_list.push(component_cell);
-            } else {
-                panic!("Component index `{index}`is out of storage len `{components_list_len}`");
-            }
+                .expect("Entity should match container")
+                .push(component);
+            self.trackers
+                .get_mut(&component_type_id)
+                .expect("Entity should match container")
+                .push(UnsafeCell::new(tracker));
         }
 
-        index
+        self.len += 1;
+        self.len - 1
     }
 
+    /// Removes the entity from the row, the last entity is moved into its place
     pub fn remove(&mut self, index: usize) -> Entity {
         let mut entity = Entity::empty();
-        for (type_id, list) in self.data.iter_mut() {
-            if let Some(component) = list[index].take() {
-                entity.set_raw(*type_id, component.into_inner());
-            }
+        for (type_id, column) in self.data.iter_mut() {
+            entity.set_raw(*type_id, column.swap_remove(index));
+        }
+        for list in self.trackers.values_mut() {
+            list.swap_remove(index);
         }
-        self.removed.push(index);
+        self.len -= 1;
         entity
     }
 
-    pub fn get<C: Any>(&self, entity_index: usize) -> Option<&C> {
-        self.data
-            .get(&TypeId::of::<C>())
-            .and_then(|list| list[entity_index].as_ref())
-            .map(|value| unsafe { (*(value.get())).downcast_ref::<C>().unwrap() })
+    /// Replaces component of the entity in the row and marks it as changed
+    pub fn replace(
+        &mut self,
+        index: usize,
+        component_type_id: TypeId,
+        component: Box<dyn Component>,
+    ) -> Option<Box<dyn Component>> {
+        let previous = self
+            .data
+            .get_mut(&component_type_id)?
+            .replace(index, component);
+        if let Some(tracker) = self
+            .trackers
+            .get_mut(&component_type_id)
+            .map(|list| list[index].get_mut())
+        {
+            tracker.changed = true;
+        }
+        Some(previous)
     }
 
-    #[allow(clippy::mut_from_ref)]
-    pub unsafe fn get_mut<C: Any>(&self, entity_index: usize) -> Option<&mut C> {
-        self.data
-            .get(&TypeId::of::<C>())
-            .and_then(|list| list[entity_index].as_ref())
-            .map(|value| (*(value.get())).downcast_mut::<C>().unwrap())
+    /// Returns component of the entity in the row
+    pub fn get(&self, index: usize, component_type_id: TypeId) -> Option<&dyn Any> {
+        self.data.get(&component_type_id)?.get(index)
     }
 
-    pub fn iter<C: Any>(&self) -> Iter<C> {
-        Iter {
-            inner: self.data.get(&TypeId::of::<C>()).unwrap().iter(),
-            _phantom_data: PhantomData,
+    /// Returns mutable component of the entity in the row and marks it as changed
+    pub fn get_mut(&mut self, index: usize, component_type_id: TypeId) -> Option<&mut dyn Any> {
+        let component = self.data.get_mut(&component_type_id)?.get_mut(index)?;
+        if let Some(tracker) = self
+            .trackers
+            .get_mut(&component_type_id)
+            .map(|list| list[index].get_mut())
+        {
+            tracker.changed = true;
         }
+        Some(component)
     }
 
-    pub unsafe fn iter_mut<C: Any>(&self) -> IterMut<C> {
-        IterMut {
-            inner: self.data.get(&TypeId::of::<C>()).unwrap().iter(),
-            _phantom_data: PhantomData,
-        }
+    /// Returns change trackers of all components of the entity in the row
+    pub fn trackers_of(&self, index: usize) -> HashMap<TypeId, Tracker> {
+        self.trackers
+            .iter()
+            .map(|(type_id, list)| (*type_id, unsafe { *list[index].get() }))
+            .collect()
     }
 
-    pub fn has(&self, component_type_id: TypeId) -> bool {
-        self.data.contains_key(&component_type_id)
+    /// Restores change trackers of components of the entity in the row
+    pub fn restore_trackers(&mut self, index: usize, trackers: &HashMap<TypeId, Tracker>) {
+        for (type_id, tracker) in trackers.iter() {
+            if let Some(list) = self.trackers.get_mut(type_id) {
+                *list[index].get_mut() = *tracker;
+            }
+        }
     }
 
-    pub fn count_components(&self) -> usize {
-        self.data.len()
+    /// Returns components of all rows by type
+    pub fn column<C: Any>(&self) -> Option<&[UnsafeCell<C>]> {
+        self.data
+            .get(&TypeId::of::<C>())
+            .and_then(|column| (**column).as_any().downcast_ref::<Dense<C>>())
+            .map(|column| column.as_slice())
     }
 
-    fn next_index(&mut self) -> usize {
-        let index = self.len;
-        self.len += 1;
-        index
+    /// Returns change trackers of components of all rows by type
+    pub fn trackers(&self, component_type_id: TypeId) -> Option<&[UnsafeCell<Tracker>]> {
+        self.trackers
+            .get(&component_type_id)
+            .map(|list| list.as_slice())
     }
-}
-
-pub struct Iter<'a, C> {
-    inner: std::slice::Iter<'a, Option<UnsafeCell<Box<dyn Any>>>>,
-    _phantom_data: PhantomData<C>,
-}
 
-impl<'a, C: Any> Iterator for Iter<'a, C> {
-    type Item = &'a C;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.inner.next() {
-                Some(next) => {
-                    if let Some(value) = next.as_ref() {
-                        return Some(unsafe { (*(value.get())).downcast_ref::<C>().unwrap() });
-                    }
-                }
-                None => return None,
-            }
+    /// Resets change trackers of all components
+    pub fn clear_trackers(&mut self) {
+        for tracker in self.trackers.values_mut().flat_map(|list| list.iter_mut()) {
+            *tracker.get_mut() = Tracker::default();
         }
     }
-}
 
-pub struct IterMut<'a, C> {
-    inner: std::slice::Iter<'a, Option<UnsafeCell<Box<dyn Any>>>>,
-    _phantom_data: PhantomData<C>,
-}
+    /// Returns number of stored entities
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-impl<'a, C: Any> Iterator for IterMut<'a, C> {
-    type Item = &'a mut C;
+    /// Returns true if there are no stored entities
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.inner.next() {
-                Some(next) => {
-                    if let Some(value) = next.as_ref() {
-                        return Some(
-                            (unsafe { &mut *(value.get()) })
-                                .downcast_mut::<C>()
-                                .unwrap(),
-                        );
-                    }
-                }
-                None => return None,
-            }
-        }
+    pub fn has(&self, component_type_id: TypeId) -> bool {
+        self.data.contains_key(&component_type_id)
+    }
+
+    pub fn count_components(&self) -> usize {
+        self.data.len()
     }
 }
 
@@ -281,10 +415,14 @@ impl From<&Entity> for Container {
     fn from(entity: &Entity) -> Self {
         Self {
             data: entity
+                .map
+                .iter()
+                .map(|(&type_id, component)| (type_id, (**component).column()))
+                .collect::<HashMap<_, _>>(),
+            trackers: entity
                 .archetype()
                 .map(|&type_id| (type_id, Vec::with_capacity(1)))
                 .collect::<HashMap<_, _>>(),
-            removed: Vec::new(),
             len: 0,
         }
     }